@@ -0,0 +1,357 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use serde_json::{Value, json};
+
+use crate::config::AppConfig;
+
+mod anthropic;
+mod local;
+mod offline;
+mod openai;
+
+pub use anthropic::AnthropicBackend;
+pub use local::{LocalApi, LocalBackend};
+pub use offline::OfflineBackend;
+pub use openai::OpenAiBackend;
+
+/// Name of the function/tool the model is asked to call in structured mode.
+pub const RESPONSE_TOOL: &str = "emit_command";
+
+/// JSON schema for structured responses, shared by every backend that supports them.
+pub static RESPONSE_SCHEMA: Lazy<Value> = Lazy::new(|| {
+    json!({
+        "type": "object",
+        "properties": {
+            "command": {
+                "type": "string",
+                "description": "A single command for the target shell, or a line starting with '#' when only guidance is possible."
+            },
+            "alternatives": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Other safe commands that achieve the same task."
+            },
+            "explanation": { "type": "string", "description": "Short justification or warnings." },
+            "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+            "requires_sudo": { "type": "boolean" },
+            "destructive": {
+                "type": "boolean",
+                "description": "True when the command deletes or overwrites data."
+            }
+        },
+        "required": ["command", "alternatives", "explanation", "confidence", "requires_sudo", "destructive"],
+        "additionalProperties": false
+    })
+});
+
+/// System and user prompts handed to a backend for a single completion.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub system: String,
+    pub user: String,
+    /// Ask for a [`RESPONSE_SCHEMA`] JSON object instead of free text.
+    pub structured: bool,
+    /// The shell the command is for.
+    pub shell: String,
+}
+
+/// Static description of what a backend requires and supports.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    /// Environment variable holding the credential this backend needs, if any.
+    pub credential_env: Option<&'static str>,
+    /// Whether the backend can be asked for a [`RESPONSE_SCHEMA`] JSON object.
+    pub structured_output: bool,
+}
+
+/// Callback receiving completion text fragments as a backend streams them.
+pub type TokenSink<'a> = dyn FnMut(&str) + Send + 'a;
+
+/// Failure reported by a backend, classified so the generator can decide whether to retry.
+#[derive(Debug)]
+pub enum BackendError {
+    Auth(String),
+    RateLimited(String),
+    Timeout,
+    /// The provider could not be reached, e.g. a local server that is not running or no network.
+    Unavailable(String),
+    Other(anyhow::Error),
+}
+
+impl BackendError {
+    /// Authentication failures will not succeed on retry; everything else might.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, BackendError::Auth(_))
+    }
+
+    /// Failures that say nothing about other providers, so the next one in a chain is worth trying.
+    pub fn falls_through(&self) -> bool {
+        matches!(
+            self,
+            BackendError::Auth(_)
+                | BackendError::RateLimited(_)
+                | BackendError::Timeout
+                | BackendError::Unavailable(_)
+        )
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Auth(message) => write!(f, "Authentication failed: {message}"),
+            BackendError::RateLimited(message) => write!(f, "Rate limited: {message}"),
+            BackendError::Timeout => write!(f, "Request timed out"),
+            BackendError::Unavailable(message) => write!(f, "Could not connect to {message}"),
+            BackendError::Other(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// A model provider capable of turning prompts into raw completion text.
+///
+/// Backends only speak to their provider; parsing, safety checks and retries are shared and live
+/// in [`crate::generator`].
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// Short identifier used in logs and messages.
+    fn name(&self) -> &'static str;
+
+    /// Model identifier requested from the provider.
+    fn model(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Request a single completion and return its raw text content.
+    async fn generate(&self, prompt: &Prompt) -> Result<String, BackendError>;
+
+    /// Request a completion, handing text fragments to `on_token` as they arrive, and return the
+    /// full content.
+    ///
+    /// Backends without native streaming deliver the whole completion as a single fragment.
+    async fn generate_stream(
+        &self,
+        prompt: &Prompt,
+        on_token: &mut TokenSink<'_>,
+    ) -> Result<String, BackendError> {
+        let content = self.generate(prompt).await?;
+        on_token(&content);
+        Ok(content)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    Openai,
+    Anthropic,
+    Ollama,
+    LlamaCpp,
+    Offline,
+}
+
+impl BackendKind {
+    pub fn from_str_case_insensitive(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "openai" => Some(BackendKind::Openai),
+            "anthropic" => Some(BackendKind::Anthropic),
+            "ollama" => Some(BackendKind::Ollama),
+            "llama-cpp" | "llamacpp" | "llama.cpp" => Some(BackendKind::LlamaCpp),
+            "offline" => Some(BackendKind::Offline),
+            _ => None,
+        }
+    }
+}
+
+/// Construct the backend for `kind` from config, with `model` overriding any configured model.
+pub fn build(kind: BackendKind, config: &AppConfig, model: Option<&str>) -> Box<dyn Backend> {
+    match kind {
+        BackendKind::Openai => Box::new(OpenAiBackend::new(
+            model
+                .or(config.model.as_deref())
+                .unwrap_or(openai::DEFAULT_MODEL)
+                .to_string(),
+            config.openai.clone(),
+        )),
+        BackendKind::Anthropic => Box::new(AnthropicBackend::new(
+            config
+                .anthropic
+                .api_base
+                .clone()
+                .unwrap_or_else(|| anthropic::DEFAULT_API_BASE.to_string()),
+            model
+                .or(config.anthropic.model.as_deref())
+                .unwrap_or(anthropic::DEFAULT_MODEL)
+                .to_string(),
+        )),
+        BackendKind::Ollama | BackendKind::LlamaCpp => {
+            let api = if kind == BackendKind::Ollama {
+                LocalApi::Ollama
+            } else {
+                LocalApi::LlamaCpp
+            };
+            Box::new(LocalBackend::new(
+                api,
+                config
+                    .local
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| api.default_base_url().to_string()),
+                model
+                    .or(config.local.model.as_deref())
+                    .unwrap_or(local::DEFAULT_MODEL)
+                    .to_string(),
+            ))
+        }
+        BackendKind::Offline => Box::new(OfflineBackend),
+    }
+}
+
+/// Stable identifier for a backend and model pair, e.g. `openai:gpt-4o-mini`.
+pub fn label(backend: &dyn Backend) -> String {
+    format!("{}:{}", backend.name(), backend.model())
+}
+
+/// Send a JSON request and return the body of a successful response, classifying failures.
+async fn send_json(request: reqwest::RequestBuilder, url: &str) -> Result<Vec<u8>, BackendError> {
+    let response = request.send().await.map_err(|err| {
+        if err.is_timeout() {
+            BackendError::Timeout
+        } else if err.is_connect() {
+            BackendError::Unavailable(format!("{url}: {err}"))
+        } else {
+            BackendError::Other(anyhow!(err))
+        }
+    })?;
+
+    let status = response.status();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|err| BackendError::Other(anyhow!(err)))?;
+
+    if status.is_success() {
+        return Ok(bytes.to_vec());
+    }
+
+    let body = String::from_utf8_lossy(&bytes).trim().to_string();
+    Err(status_error(status, body, url))
+}
+
+/// Classify an unsuccessful HTTP response by its status.
+fn status_error(status: reqwest::StatusCode, body: String, url: &str) -> BackendError {
+    match status.as_u16() {
+        401 | 403 => BackendError::Auth(body),
+        // 529 is Anthropic's "overloaded" status; back off the same way as a rate limit.
+        429 | 529 => BackendError::RateLimited(body),
+        _ => BackendError::Other(anyhow!("{url} returned {status}: {body}")),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use std::{env, ffi::OsString};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+
+    /// Sets an environment variable until dropped, then restores its previous value.
+    pub struct EnvVar {
+        name: &'static str,
+        previous: Option<OsString>,
+    }
+
+    impl EnvVar {
+        pub fn set(name: &'static str, value: &str) -> Self {
+            let previous = env::var_os(name);
+            // SAFETY: tests touching the environment run `#[serial]`.
+            unsafe { env::set_var(name, value) };
+            Self { name, previous }
+        }
+    }
+
+    impl Drop for EnvVar {
+        fn drop(&mut self) {
+            // SAFETY: as in `set`.
+            unsafe {
+                match &self.previous {
+                    Some(value) => env::set_var(self.name, value),
+                    None => env::remove_var(self.name),
+                }
+            }
+        }
+    }
+
+    /// Serve one canned JSON response on an ephemeral port, yielding the raw request received.
+    pub async fn spawn_stub(status: u16, body: &str) -> (String, JoinHandle<String>) {
+        spawn_response(status, "application/json", body).await
+    }
+
+    /// Serve one canned server-sent event stream, as used by streaming chat completions.
+    pub async fn spawn_event_stream(body: &str) -> (String, JoinHandle<String>) {
+        spawn_response(200, "text/event-stream", body).await
+    }
+
+    /// The URL of a local port nothing listens on, like a server that is not running.
+    pub async fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    async fn spawn_response(
+        status: u16,
+        content_type: &'static str,
+        body: &str,
+    ) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = body.to_string();
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            let response = format!(
+                "HTTP/1.1 {status} Stub\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let _ = socket.shutdown().await;
+            request
+        });
+
+        (format!("http://{addr}"), handle)
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> String {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            let read = socket.read(&mut chunk).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+
+            if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&buffer[..end]).to_lowercase();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if buffer.len() >= end + 4 + length {
+                    break;
+                }
+            }
+        }
+
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
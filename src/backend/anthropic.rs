@@ -0,0 +1,232 @@
+use std::env;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::trace;
+
+use super::{
+    Backend, BackendError, Capabilities, Prompt, RESPONSE_SCHEMA, RESPONSE_TOOL, send_json,
+};
+
+/// Anthropic model used when no model is configured.
+pub const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+pub const DEFAULT_API_BASE: &str = "https://api.anthropic.com";
+const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const API_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 1024;
+
+/// Backend for the Anthropic Messages API.
+pub struct AnthropicBackend {
+    api_base: String,
+    model: String,
+    http: reqwest::Client,
+}
+
+impl AnthropicBackend {
+    pub fn new(api_base: String, model: String) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            model,
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        input: Value,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[async_trait]
+impl Backend for AnthropicBackend {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            credential_env: Some(API_KEY_ENV),
+            structured_output: true,
+        }
+    }
+
+    async fn generate(&self, prompt: &Prompt) -> Result<String, BackendError> {
+        let api_key = env::var(API_KEY_ENV)
+            .ok()
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| {
+                BackendError::Auth(format!(
+                    "{API_KEY_ENV} missing. Set it as an environment variable or in your .env file"
+                ))
+            })?;
+
+        let url = format!("{}/v1/messages", self.api_base);
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": MAX_TOKENS,
+            "temperature": 0.2,
+            "system": prompt.system,
+            "messages": [{ "role": "user", "content": prompt.user }],
+        });
+        if prompt.structured {
+            body["tools"] = json!([{
+                "name": RESPONSE_TOOL,
+                "description": "Return the generated shell command.",
+                "input_schema": *RESPONSE_SCHEMA,
+            }]);
+            body["tool_choice"] = json!({ "type": "tool", "name": RESPONSE_TOOL });
+        }
+
+        trace!(%url, "Dispatching Anthropic messages request");
+        let request = self
+            .http
+            .post(&url)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body);
+        let bytes = send_json(request, &url).await?;
+
+        let response: MessagesResponse = serde_json::from_slice(&bytes).map_err(|err| {
+            BackendError::Other(anyhow!("Unexpected response from Anthropic: {err}"))
+        })?;
+
+        Ok(render_content(response.content, prompt.structured))
+    }
+}
+
+/// Flatten content blocks into text the generator parses.
+///
+/// In structured mode the tool input is returned as JSON; otherwise tool input is rendered back
+/// into `Command:`/`Explanation:` lines.
+fn render_content(blocks: Vec<ContentBlock>, structured: bool) -> String {
+    let mut parts = Vec::new();
+
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text } => parts.push(text),
+            ContentBlock::ToolUse { input } if structured => {
+                return input.to_string();
+            }
+            ContentBlock::ToolUse { input } => {
+                let field = |name: &str| input.get(name).and_then(Value::as_str).map(str::trim);
+                if let Some(command) = field("command") {
+                    let mut rendered = format!("Command: {command}");
+                    if let Some(explanation) = field("explanation") {
+                        rendered.push_str(&format!("\nExplanation: {explanation}"));
+                    }
+                    parts.push(rendered);
+                }
+            }
+            ContentBlock::Unsupported => {}
+        }
+    }
+
+    parts.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_support::{EnvVar, spawn_stub};
+    use serial_test::serial;
+
+    fn prompt() -> Prompt {
+        Prompt {
+            system: "You are an expert bash assistant.".to_string(),
+            user: "Description: list files".to_string(),
+            structured: false,
+            shell: "bash".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn sends_system_prompt_and_joins_text_blocks() {
+        let (base_url, request) = spawn_stub(
+            200,
+            r#"{"id":"msg_1","type":"message","role":"assistant","content":[{"type":"text","text":"Command: ls -la"},{"type":"text","text":"Explanation: List files"}],"stop_reason":"end_turn"}"#,
+        )
+        .await;
+        let _key = EnvVar::set(API_KEY_ENV, "sk-ant-test");
+
+        let backend = AnthropicBackend::new(base_url, DEFAULT_MODEL.to_string());
+        let content = backend.generate(&prompt()).await.expect("stub responds");
+
+        assert_eq!(content, "Command: ls -la\nExplanation: List files");
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /v1/messages"));
+        assert!(request.to_lowercase().contains("x-api-key: sk-ant-test"));
+        assert!(request.contains(r#""system":"You are an expert bash assistant.""#));
+        assert!(request.contains(r#""role":"user""#));
+    }
+
+    #[test]
+    fn renders_tool_use_blocks_as_command_lines() {
+        let blocks = vec![ContentBlock::ToolUse {
+            input: json!({ "command": "df -h", "explanation": "Show disk usage" }),
+        }];
+
+        assert_eq!(
+            render_content(blocks, false),
+            "Command: df -h\nExplanation: Show disk usage"
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn structured_mode_returns_tool_input_json() {
+        let (base_url, request) = spawn_stub(
+            200,
+            r#"{"content":[{"type":"tool_use","id":"toolu_1","name":"emit_command","input":{"command":"df -h","alternatives":[],"explanation":"Disk space","confidence":0.9,"requires_sudo":false,"destructive":false}}]}"#,
+        )
+        .await;
+        let _key = EnvVar::set(API_KEY_ENV, "sk-ant-test");
+
+        let backend = AnthropicBackend::new(base_url, DEFAULT_MODEL.to_string());
+        let structured = Prompt {
+            structured: true,
+            ..prompt()
+        };
+        let content = backend.generate(&structured).await.expect("stub responds");
+
+        let value: Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(value["command"], "df -h");
+        let request = request.await.unwrap();
+        assert!(request.contains(r#""tool_choice":{"name":"emit_command","type":"tool"}"#));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn overloaded_responses_are_rate_limited() {
+        let (base_url, _request) = spawn_stub(
+            529,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        )
+        .await;
+        let _key = EnvVar::set(API_KEY_ENV, "sk-ant-test");
+
+        let backend = AnthropicBackend::new(base_url, DEFAULT_MODEL.to_string());
+        let err = backend.generate(&prompt()).await.expect_err("529 fails");
+
+        assert!(matches!(err, BackendError::RateLimited(_)));
+    }
+}
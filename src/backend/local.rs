@@ -0,0 +1,228 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::trace;
+
+use super::{Backend, BackendError, Capabilities, Prompt, RESPONSE_SCHEMA, send_json};
+
+/// Model requested from a local server when none is configured.
+pub const DEFAULT_MODEL: &str = "llama3.2";
+
+/// HTTP dialect spoken by the local model server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalApi {
+    /// Ollama's native `/api/chat` endpoint.
+    Ollama,
+    /// llama.cpp's `llama-server`, via its OpenAI-compatible `/v1/chat/completions` endpoint.
+    LlamaCpp,
+}
+
+impl LocalApi {
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            LocalApi::Ollama => "http://localhost:11434",
+            LocalApi::LlamaCpp => "http://localhost:8080",
+        }
+    }
+}
+
+/// Backend for a model server running on the local machine or network, requiring no credentials.
+pub struct LocalBackend {
+    api: LocalApi,
+    base_url: String,
+    model: String,
+    http: reqwest::Client,
+}
+
+impl LocalBackend {
+    pub fn new(api: LocalApi, base_url: String, model: String) -> Self {
+        Self {
+            api,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatMessage,
+}
+
+#[async_trait]
+impl Backend for LocalBackend {
+    fn name(&self) -> &'static str {
+        match self.api {
+            LocalApi::Ollama => "ollama",
+            LocalApi::LlamaCpp => "llama-cpp",
+        }
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            credential_env: None,
+            structured_output: true,
+        }
+    }
+
+    async fn generate(&self, prompt: &Prompt) -> Result<String, BackendError> {
+        let messages = json!([
+            { "role": "system", "content": prompt.system },
+            { "role": "user", "content": prompt.user },
+        ]);
+
+        let (url, mut body) = match self.api {
+            LocalApi::Ollama => (
+                format!("{}/api/chat", self.base_url),
+                json!({
+                    "model": self.model,
+                    "messages": messages,
+                    "stream": false,
+                    "options": { "temperature": 0.2 },
+                }),
+            ),
+            LocalApi::LlamaCpp => (
+                format!("{}/v1/chat/completions", self.base_url),
+                json!({
+                    "model": self.model,
+                    "messages": messages,
+                    "stream": false,
+                    "temperature": 0.2,
+                }),
+            ),
+        };
+
+        // Servers that ignore the schema still answer in text, which the generator falls back to.
+        if prompt.structured {
+            match self.api {
+                LocalApi::Ollama => body["format"] = RESPONSE_SCHEMA.clone(),
+                LocalApi::LlamaCpp => {
+                    body["response_format"] =
+                        json!({ "type": "json_object", "schema": *RESPONSE_SCHEMA })
+                }
+            }
+        }
+
+        trace!(%url, "Dispatching local model request");
+        let bytes = send_json(self.http.post(&url).json(&body), &url).await?;
+
+        let content = match self.api {
+            LocalApi::Ollama => serde_json::from_slice::<OllamaResponse>(&bytes)
+                .map(|response| response.message.content),
+            LocalApi::LlamaCpp => {
+                serde_json::from_slice::<ChatCompletionResponse>(&bytes).map(|response| {
+                    response
+                        .choices
+                        .into_iter()
+                        .next()
+                        .map(|choice| choice.message.content)
+                        .unwrap_or_default()
+                })
+            }
+        }
+        .map_err(|err| BackendError::Other(anyhow!("Unexpected response from {url}: {err}")))?;
+
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_support::{closed_port, spawn_stub};
+
+    fn prompt() -> Prompt {
+        Prompt {
+            system: "You are an expert bash assistant.".to_string(),
+            user: "Description: list files".to_string(),
+            structured: false,
+            shell: "bash".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn ollama_returns_message_content() {
+        let (base_url, request) = spawn_stub(
+            200,
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"Command: ls\nExplanation: List files"},"done":true}"#,
+        )
+        .await;
+
+        let backend = LocalBackend::new(LocalApi::Ollama, base_url, "llama3.2".to_string());
+        let content = backend.generate(&prompt()).await.expect("stub responds");
+
+        assert_eq!(content, "Command: ls\nExplanation: List files");
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /api/chat"));
+        assert!(request.contains(r#""model":"llama3.2""#));
+        assert!(request.contains(r#""stream":false"#));
+    }
+
+    #[tokio::test]
+    async fn llama_cpp_returns_first_choice() {
+        let (base_url, request) = spawn_stub(
+            200,
+            r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"Command: pwd\nExplanation: Print directory"}}]}"#,
+        )
+        .await;
+
+        let backend = LocalBackend::new(LocalApi::LlamaCpp, base_url, "local".to_string());
+        let content = backend.generate(&prompt()).await.expect("stub responds");
+
+        assert_eq!(content, "Command: pwd\nExplanation: Print directory");
+        assert!(
+            request
+                .await
+                .unwrap()
+                .starts_with("POST /v1/chat/completions")
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_server_errors() {
+        let (base_url, _request) = spawn_stub(404, r#"{"error":"model 'nope' not found"}"#).await;
+
+        let backend = LocalBackend::new(LocalApi::Ollama, base_url, "nope".to_string());
+        let err = backend.generate(&prompt()).await.expect_err("404 fails");
+
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_unavailable() {
+        let backend = LocalBackend::new(
+            LocalApi::Ollama,
+            closed_port().await,
+            "llama3.2".to_string(),
+        );
+        let err = backend
+            .generate(&prompt())
+            .await
+            .expect_err("nothing listens");
+
+        assert!(matches!(err, BackendError::Unavailable(_)), "{err}");
+        assert!(err.falls_through());
+    }
+}
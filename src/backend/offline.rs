@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+
+use super::{Backend, BackendError, Capabilities, Prompt};
+
+/// Keyword rules tried in order: every keyword must appear in the description as a word, alone or
+/// in its plural. The last field lists shells whose builtins take different arguments.
+static RULES: &[(&[&str], &str, &str, &[&str])] = &[
+    (
+        &["large", "file"],
+        "find . -type f -size +100M",
+        "Finds files larger than 100 MB below the current directory.",
+        &["nu"],
+    ),
+    (
+        &["disk", "usage"],
+        "du -sh *",
+        "Summarizes disk usage of each entry in the current directory.",
+        &["nu"],
+    ),
+    (
+        &["disk", "space"],
+        "df -h",
+        "Shows free and used space on mounted filesystems.",
+        &[],
+    ),
+    (
+        &["memory"],
+        "free -h",
+        "Shows memory usage in human-readable units.",
+        &[],
+    ),
+    (
+        &["listening", "port"],
+        "lsof -nP -iTCP -sTCP:LISTEN",
+        "Lists processes listening on TCP ports.",
+        &[],
+    ),
+    (
+        &["process"],
+        "ps aux",
+        "Lists running processes for all users.",
+        &["nu"],
+    ),
+    (
+        &["git", "status"],
+        "git status --short",
+        "Shows the working tree status in short format.",
+        &[],
+    ),
+    (
+        &["staged"],
+        "git diff --cached --stat",
+        "Summarizes changes staged for commit.",
+        &[],
+    ),
+    (
+        &["git", "log"],
+        "git log --oneline -n 20",
+        "Shows the last 20 commits, one per line.",
+        &[],
+    ),
+    (
+        &["hostname"],
+        "hostname",
+        "Prints the name of this machine.",
+        &[],
+    ),
+    (
+        &["ip", "address"],
+        "ip addr show",
+        "Shows network interfaces and their addresses.",
+        &[],
+    ),
+    (
+        &["current", "directory"],
+        "pwd",
+        "Prints the current working directory.",
+        &[],
+    ),
+    (
+        &["list", "file"],
+        "ls -la",
+        "Lists files in the current directory, including hidden ones.",
+        &[],
+    ),
+];
+
+/// Last-resort backend that matches the description against a small table of common tasks.
+///
+/// It never leaves the machine, so it works when every remote provider is unavailable.
+pub struct OfflineBackend;
+
+#[async_trait]
+impl Backend for OfflineBackend {
+    fn name(&self) -> &'static str {
+        "offline"
+    }
+
+    fn model(&self) -> &str {
+        "rules"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            credential_env: None,
+            structured_output: false,
+        }
+    }
+
+    async fn generate(&self, prompt: &Prompt) -> Result<String, BackendError> {
+        let description = prompt
+            .user
+            .strip_prefix("Description:")
+            .unwrap_or(&prompt.user)
+            .to_lowercase();
+        let words: Vec<&str> = description
+            .split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let matched = RULES
+            .iter()
+            .filter(|(_, _, _, shells)| !shells.contains(&prompt.shell.as_str()))
+            .find(|(keywords, _, _, _)| {
+                keywords.iter().all(|keyword| {
+                    words.iter().any(|word| {
+                        word.strip_prefix(keyword)
+                            .is_some_and(|rest| matches!(rest, "" | "s" | "es"))
+                    })
+                })
+            });
+
+        Ok(match matched {
+            Some((_, command, explanation, _)) => {
+                format!("Command: {command}\nExplanation: {explanation}")
+            }
+            None => "Command: # No offline rule matches this description; try again when a model provider is reachable.\nExplanation: Offline mode only knows a handful of common tasks.".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(description: &str) -> Prompt {
+        Prompt {
+            system: String::new(),
+            user: format!("Description: {description}"),
+            structured: false,
+            shell: "bash".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn matches_known_tasks() {
+        let content = OfflineBackend
+            .generate(&prompt("Check disk usage here"))
+            .await
+            .unwrap();
+        assert!(content.starts_with("Command: du -sh *"));
+    }
+
+    #[tokio::test]
+    async fn matches_whole_words_for_the_target_shell() {
+        let content = OfflineBackend
+            .generate(&prompt("list the files here"))
+            .await
+            .unwrap();
+        assert!(content.starts_with("Command: ls -la"));
+
+        // "zip" and "skipped" contain "ip", but no address is asked for.
+        let content = OfflineBackend
+            .generate(&prompt("zip the skipped address book"))
+            .await
+            .unwrap();
+        assert!(content.starts_with("Command: #"), "{content}");
+
+        let nu = Prompt {
+            shell: "nu".to_string(),
+            ..prompt("check disk usage")
+        };
+        let content = OfflineBackend.generate(&nu).await.unwrap();
+        assert!(content.starts_with("Command: #"), "{content}");
+    }
+
+    #[tokio::test]
+    async fn unknown_tasks_return_guidance() {
+        let content = OfflineBackend
+            .generate(&prompt("rotate the nginx certificates"))
+            .await
+            .unwrap();
+        assert!(content.starts_with("Command: #"));
+    }
+}
@@ -0,0 +1,571 @@
+use std::env;
+
+use anyhow::{Context, Result, anyhow};
+use async_openai::{
+    Client,
+    config::{AzureConfig, Config, OpenAIConfig},
+    error::OpenAIError,
+    types::{
+        ChatCompletionNamedToolChoice, ChatCompletionRequestMessage,
+        ChatCompletionRequestSystemMessage, ChatCompletionRequestUserMessage,
+        ChatCompletionRequestUserMessageContent, ChatCompletionTool,
+        ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequest,
+        CreateChatCompletionRequestArgs, CreateChatCompletionResponse, FunctionName,
+        FunctionObject, Role,
+    },
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use secrecy::Secret;
+use tracing::trace;
+
+use super::{
+    Backend, BackendError, Capabilities, Prompt, RESPONSE_SCHEMA, RESPONSE_TOOL, TokenSink,
+    send_json, status_error,
+};
+use crate::config::OpenAiSettings;
+
+/// OpenAI chat model used when no model is configured.
+pub const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+const API_KEY_ENV: &str = "OPENAI_API_KEY";
+const AZURE_API_KEY_ENV: &str = "AZURE_OPENAI_API_KEY";
+const AZURE_DEFAULT_API_VERSION: &str = "2024-02-01";
+const PROJECT_HEADER: &str = "OpenAI-Project";
+
+/// Backend for the OpenAI chat completions API and compatible gateways, proxies and Azure.
+pub struct OpenAiBackend {
+    model: String,
+    settings: OpenAiSettings,
+    http: reqwest::Client,
+}
+
+impl OpenAiBackend {
+    pub fn new(model: String, settings: OpenAiSettings) -> Self {
+        Self {
+            model,
+            settings,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn api_key_env(&self) -> &'static str {
+        if self.settings.azure_deployment.is_some() {
+            AZURE_API_KEY_ENV
+        } else {
+            API_KEY_ENV
+        }
+    }
+
+    fn config(&self) -> Result<EndpointConfig, BackendError> {
+        let key_env = self.api_key_env();
+        let api_key = env::var(key_env).map_err(|_| {
+            BackendError::Auth(format!(
+                "{key_env} missing. Set it as an environment variable or in your .env file"
+            ))
+        })?;
+
+        if api_key.trim().is_empty() {
+            return Err(BackendError::Auth(format!("{key_env} is empty")));
+        }
+
+        let settings = &self.settings;
+        let endpoint = match &settings.azure_deployment {
+            Some(deployment) => {
+                let api_base = settings.api_base.clone().ok_or_else(|| {
+                    BackendError::Other(anyhow!(
+                        "Azure OpenAI requires `api_base` set to your resource endpoint"
+                    ))
+                })?;
+                Endpoint::Azure(
+                    AzureConfig::new()
+                        .with_api_base(api_base)
+                        .with_deployment_id(deployment)
+                        .with_api_version(
+                            settings
+                                .azure_api_version
+                                .as_deref()
+                                .unwrap_or(AZURE_DEFAULT_API_VERSION),
+                        )
+                        .with_api_key(api_key),
+                )
+            }
+            None => {
+                let mut config = OpenAIConfig::new().with_api_key(api_key);
+                if let Some(api_base) = &settings.api_base {
+                    config = config.with_api_base(api_base.trim_end_matches('/'));
+                }
+                if let Some(org_id) = &settings.org_id {
+                    config = config.with_org_id(org_id);
+                }
+                Endpoint::OpenAi(config)
+            }
+        };
+
+        let mut headers = HeaderMap::new();
+        let project = settings
+            .project
+            .as_ref()
+            .map(|project| (PROJECT_HEADER, project));
+        for (name, value) in project
+            .into_iter()
+            .chain(settings.headers.iter().map(|(k, v)| (k.as_str(), v)))
+        {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| {
+                BackendError::Other(anyhow!("Invalid header name '{name}': {err}"))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|err| {
+                BackendError::Other(anyhow!("Invalid value for header '{name}': {err}"))
+            })?;
+            headers.insert(name, value);
+        }
+
+        Ok(EndpointConfig { endpoint, headers })
+    }
+}
+
+/// Client configuration for either endpoint flavour, plus headers added to every request.
+#[derive(Clone)]
+struct EndpointConfig {
+    endpoint: Endpoint,
+    headers: HeaderMap,
+}
+
+#[derive(Clone)]
+enum Endpoint {
+    OpenAi(OpenAIConfig),
+    Azure(AzureConfig),
+}
+
+impl Config for EndpointConfig {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = match &self.endpoint {
+            Endpoint::OpenAi(config) => config.headers(),
+            Endpoint::Azure(config) => config.headers(),
+        };
+        headers.extend(self.headers.clone());
+        headers
+    }
+
+    fn url(&self, path: &str) -> String {
+        match &self.endpoint {
+            Endpoint::OpenAi(config) => config.url(path),
+            Endpoint::Azure(config) => config.url(path),
+        }
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        match &self.endpoint {
+            Endpoint::OpenAi(config) => config.query(),
+            Endpoint::Azure(config) => config.query(),
+        }
+    }
+
+    fn api_base(&self) -> &str {
+        match &self.endpoint {
+            Endpoint::OpenAi(config) => config.api_base(),
+            Endpoint::Azure(config) => config.api_base(),
+        }
+    }
+
+    fn api_key(&self) -> &Secret<String> {
+        match &self.endpoint {
+            Endpoint::OpenAi(config) => config.api_key(),
+            Endpoint::Azure(config) => config.api_key(),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for OpenAiBackend {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let settings = &self.settings;
+        let official = settings.api_base.is_none() || settings.azure_deployment.is_some();
+        Capabilities {
+            credential_env: Some(self.api_key_env()),
+            structured_output: settings.structured_output.unwrap_or(official),
+        }
+    }
+
+    async fn generate(&self, prompt: &Prompt) -> Result<String, BackendError> {
+        let config = self.config()?;
+        let request = build_chat_request(&self.model, prompt).map_err(BackendError::Other)?;
+
+        // Sent directly rather than through the client, which drops the HTTP status of failures.
+        let url = config.url("/chat/completions");
+        let bytes = send_json(
+            self.http
+                .post(&url)
+                .query(&config.query())
+                .headers(config.headers())
+                .json(&request),
+            &url,
+        )
+        .await?;
+        let response: CreateChatCompletionResponse = serde_json::from_slice(&bytes)
+            .context("OpenAI response was not a chat completion")
+            .map_err(BackendError::Other)?;
+
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .context("OpenAI response did not contain any choices")
+            .map_err(BackendError::Other)?;
+
+        trace!(?choice.message, "raw choice message");
+
+        let mut content = choice.message.content.unwrap_or_default();
+
+        let needs_fallback = content.trim().is_empty();
+        if let Some(tool_calls) = choice.message.tool_calls.filter(|_| needs_fallback) {
+            let fallback = tool_calls
+                .into_iter()
+                .map(|call| call.function.arguments)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !fallback.trim().is_empty() {
+                content = fallback;
+            }
+        }
+
+        Ok(content)
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &Prompt,
+        on_token: &mut TokenSink<'_>,
+    ) -> Result<String, BackendError> {
+        let client = Client::with_config(self.config()?);
+        let request = build_chat_request(&self.model, prompt).map_err(BackendError::Other)?;
+
+        let mut stream = client
+            .chat()
+            .create_stream(request)
+            .await
+            .map_err(classify_error)?;
+
+        let mut content = String::new();
+        let mut tool_arguments = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(classify_error)?;
+            let Some(choice) = chunk.choices.into_iter().next() else {
+                continue;
+            };
+
+            if let Some(token) = choice.delta.content.filter(|token| !token.is_empty()) {
+                on_token(&token);
+                content.push_str(&token);
+            }
+            for call in choice.delta.tool_calls.into_iter().flatten() {
+                if let Some(arguments) = call.function.and_then(|function| function.arguments) {
+                    tool_arguments.push_str(&arguments);
+                }
+            }
+        }
+
+        if content.trim().is_empty() && !tool_arguments.trim().is_empty() {
+            on_token(&tool_arguments);
+            content = tool_arguments;
+        }
+
+        Ok(content)
+    }
+}
+
+/// Build a chat completion request from the system and user prompts.
+///
+/// Structured prompts force a call to the [`RESPONSE_TOOL`] function, whose arguments are the
+/// JSON object the generator reads.
+fn build_chat_request(model: &str, prompt: &Prompt) -> Result<CreateChatCompletionRequest> {
+    let mut args = CreateChatCompletionRequestArgs::default();
+    args.model(model).temperature(0.2).messages(vec![
+        ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+            content: prompt.system.clone(),
+            role: Role::System,
+            name: None,
+        }),
+        ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
+            content: ChatCompletionRequestUserMessageContent::Text(prompt.user.clone()),
+            role: Role::User,
+            name: None,
+        }),
+    ]);
+
+    if prompt.structured {
+        args.tools(vec![ChatCompletionTool {
+            r#type: ChatCompletionToolType::Function,
+            function: FunctionObject {
+                name: RESPONSE_TOOL.to_string(),
+                description: Some("Return the generated shell command.".to_string()),
+                parameters: Some(RESPONSE_SCHEMA.clone()),
+            },
+        }])
+        .tool_choice(ChatCompletionToolChoiceOption::Named(
+            ChatCompletionNamedToolChoice {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionName {
+                    name: RESPONSE_TOOL.to_string(),
+                },
+            },
+        ));
+    }
+
+    Ok(args.build()?)
+}
+
+/// Map OpenAI client errors onto the shared backend error classes.
+fn classify_error(err: OpenAIError) -> BackendError {
+    match &err {
+        OpenAIError::ApiError(api) => {
+            let code = api
+                .code
+                .as_ref()
+                .and_then(|code| code.as_str())
+                .unwrap_or_default();
+            let message = api.message.to_lowercase();
+            if code == "rate_limit_exceeded" || message.contains("rate limit") {
+                BackendError::RateLimited(api.message.clone())
+            } else if code == "invalid_api_key" || message.contains("api key") {
+                BackendError::Auth(api.message.clone())
+            } else {
+                BackendError::Other(anyhow!(err))
+            }
+        }
+        OpenAIError::Reqwest(inner) if inner.is_timeout() => BackendError::Timeout,
+        OpenAIError::Reqwest(inner) if inner.is_connect() => {
+            BackendError::Unavailable(format!("the OpenAI API: {inner}"))
+        }
+        // Streaming requests only report the HTTP status as text, e.g. "Invalid status code: 429".
+        OpenAIError::StreamError(message) if let Some(status) = stream_status(message) => {
+            status_error(status, message.clone(), "the OpenAI API")
+        }
+        // The request never got through, e.g. "error sending request for url (...)".
+        OpenAIError::StreamError(message) if message.starts_with("error sending request") => {
+            BackendError::Unavailable(format!("the OpenAI API: {message}"))
+        }
+        _ => BackendError::Other(anyhow!(err)),
+    }
+}
+
+/// The HTTP status in an event stream's "Invalid status code: 401 Unauthorized" error.
+fn stream_status(message: &str) -> Option<reqwest::StatusCode> {
+    let code = message
+        .strip_prefix("Invalid status code: ")?
+        .split_whitespace()
+        .next()?;
+    reqwest::StatusCode::from_bytes(code.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_support::{EnvVar, spawn_event_stream, spawn_stub};
+    use async_openai::error::ApiError;
+    use serial_test::serial;
+
+    fn api_error(message: &str, code: Option<&str>) -> OpenAIError {
+        OpenAIError::ApiError(ApiError {
+            message: message.to_string(),
+            r#type: None,
+            param: None,
+            code: code.map(|code| code.into()),
+        })
+    }
+
+    #[test]
+    fn classifies_api_errors() {
+        assert!(matches!(
+            classify_error(api_error("Rate limit reached", None)),
+            BackendError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify_error(api_error(
+                "Incorrect API key provided",
+                Some("invalid_api_key")
+            )),
+            BackendError::Auth(_)
+        ));
+        assert!(matches!(
+            classify_error(api_error("The server had an error", None)),
+            BackendError::Other(_)
+        ));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn gateway_rejections_are_auth_failures_by_status() {
+        let _key = EnvVar::set(API_KEY_ENV, "sk-test");
+        let gateway = |base_url: String| {
+            OpenAiBackend::new(
+                "gpt-4o-mini".to_string(),
+                OpenAiSettings {
+                    api_base: Some(base_url),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let (base_url, _request) = spawn_stub(401, "Unauthorized").await;
+        let err = gateway(base_url)
+            .generate(&prompt())
+            .await
+            .expect_err("401 fails");
+        assert!(matches!(err, BackendError::Auth(_)), "{err}");
+
+        let (base_url, _request) = spawn_stub(401, r#"{"error":"invalid token"}"#).await;
+        let err = gateway(base_url)
+            .generate_stream(&prompt(), &mut |_| {})
+            .await
+            .expect_err("401 fails");
+        assert!(matches!(err, BackendError::Auth(_)), "{err}");
+
+        assert_eq!(
+            stream_status("Invalid status code: 429 Too Many Requests"),
+            Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+        );
+        assert_eq!(stream_status("error 401 in chunk"), None);
+    }
+
+    const COMPLETION: &str = r#"{"id":"chatcmpl-1","object":"chat.completion","created":1,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":"Command: ls\nExplanation: List files"},"finish_reason":"stop"}]}"#;
+
+    fn prompt() -> Prompt {
+        Prompt {
+            system: "You are an expert bash assistant.".to_string(),
+            user: "Description: list files".to_string(),
+            structured: false,
+            shell: "bash".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn sends_gateway_headers_to_custom_base() {
+        let (base_url, request) = spawn_stub(200, COMPLETION).await;
+        let _key = EnvVar::set(API_KEY_ENV, "sk-test");
+
+        let settings = OpenAiSettings {
+            api_base: Some(format!("{base_url}/v1")),
+            org_id: Some("org-42".to_string()),
+            project: Some("proj_7".to_string()),
+            headers: [("X-Team".to_string(), "infra".to_string())].into(),
+            ..Default::default()
+        };
+        let backend = OpenAiBackend::new("gpt-4o-mini".to_string(), settings);
+        let content = backend.generate(&prompt()).await.expect("stub responds");
+
+        assert_eq!(content, "Command: ls\nExplanation: List files");
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.starts_with("post /v1/chat/completions"));
+        assert!(request.contains("authorization: bearer sk-test"));
+        assert!(request.contains("openai-organization: org-42"));
+        assert!(request.contains("openai-project: proj_7"));
+        assert!(request.contains("x-team: infra"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn streams_content_deltas() {
+        let (base_url, request) = spawn_event_stream(concat!(
+            "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Command: ls\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"\\nExplanation: List files\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        ))
+        .await;
+        let _key = EnvVar::set(API_KEY_ENV, "sk-test");
+
+        let settings = OpenAiSettings {
+            api_base: Some(base_url),
+            ..Default::default()
+        };
+        let backend = OpenAiBackend::new("gpt-4o-mini".to_string(), settings);
+        let mut tokens = Vec::new();
+        let content = backend
+            .generate_stream(&prompt(), &mut |token| tokens.push(token.to_string()))
+            .await
+            .expect("stub streams");
+
+        assert_eq!(content, "Command: ls\nExplanation: List files");
+        assert_eq!(tokens, ["Command: ls", "\nExplanation: List files"]);
+        assert!(request.await.unwrap().contains(r#""stream":true"#));
+    }
+
+    #[test]
+    fn structured_requests_force_the_response_tool() {
+        let structured = Prompt {
+            structured: true,
+            ..prompt()
+        };
+        let request = build_chat_request("gpt-4o-mini", &structured).unwrap();
+        let encoded = serde_json::to_string(&request).unwrap();
+
+        assert!(
+            encoded.contains(
+                r#""tool_choice":{"type":"function","function":{"name":"emit_command"}}"#
+            )
+        );
+        assert!(encoded.contains(r#""requires_sudo""#));
+
+        let plain = build_chat_request("gpt-4o-mini", &prompt()).unwrap();
+        assert!(plain.tools.is_none());
+    }
+
+    #[test]
+    fn structured_output_is_opt_in_for_custom_bases() {
+        let structured = |settings: OpenAiSettings| {
+            OpenAiBackend::new("gpt-4o-mini".to_string(), settings)
+                .capabilities()
+                .structured_output
+        };
+        let gateway = || OpenAiSettings {
+            api_base: Some("http://localhost:8000/v1".to_string()),
+            ..Default::default()
+        };
+
+        assert!(structured(OpenAiSettings::default()));
+        assert!(!structured(gateway()));
+        assert!(structured(OpenAiSettings {
+            structured_output: Some(true),
+            ..gateway()
+        }));
+        assert!(structured(OpenAiSettings {
+            azure_deployment: Some("shell-gen".to_string()),
+            ..gateway()
+        }));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn azure_mode_targets_deployment() {
+        let (base_url, request) = spawn_stub(200, COMPLETION).await;
+        let _key = EnvVar::set(AZURE_API_KEY_ENV, "azure-key");
+
+        let settings = OpenAiSettings {
+            api_base: Some(base_url),
+            azure_deployment: Some("shell-gen".to_string()),
+            azure_api_version: Some("2024-06-01".to_string()),
+            ..Default::default()
+        };
+        let backend = OpenAiBackend::new("gpt-4o-mini".to_string(), settings);
+        assert_eq!(
+            backend.capabilities().credential_env,
+            Some(AZURE_API_KEY_ENV)
+        );
+        backend.generate(&prompt()).await.expect("stub responds");
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with(
+            "POST /openai/deployments/shell-gen/chat/completions?api-version=2024-06-01"
+        ));
+        assert!(request.to_lowercase().contains("api-key: azure-key"));
+    }
+}
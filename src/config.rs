@@ -1,10 +1,13 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
 
 use anyhow::{Context, Result};
 use dirs::home_dir;
 use serde::Deserialize;
 
+use crate::policy::PolicyFile;
+
 const ENV_FILE: &str = ".env";
+const DATA_DIR_ENV: &str = "TASK_SH_DATA_DIR";
 
 pub fn save_default_env(key: &str, value: &str) -> Result<()> {
     let env_path = PathBuf::from(ENV_FILE);
@@ -31,22 +34,80 @@ pub fn save_default_env(key: &str, value: &str) -> Result<()> {
         .with_context(|| format!("Failed to write {}", env_path.display()))
 }
 
+/// Connection details for a local Ollama or llama.cpp server (`[local]` table).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct LocalConfig {
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+}
+
+/// Anthropic Messages API settings (`[anthropic]` table).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct AnthropicConfig {
+    pub api_base: Option<String>,
+    pub model: Option<String>,
+}
+
+/// OpenAI-compatible endpoint settings (`[openai]` table).
+///
+/// Setting `azure_deployment` switches to Azure OpenAI, where `api_base` is the resource endpoint.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct OpenAiSettings {
+    pub api_base: Option<String>,
+    pub org_id: Option<String>,
+    pub project: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub azure_deployment: Option<String>,
+    pub azure_api_version: Option<String>,
+    /// Ask for a function call carrying the JSON response. Defaults to on for OpenAI and Azure and
+    /// off for a custom `api_base`, since many compatible servers reject the `tools` parameter.
+    pub structured_output: Option<bool>,
+}
+
+/// One entry of the ordered `[[providers]]` fallback chain.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProviderEntry {
+    pub backend: String,
+    pub model: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct FileConfig {
     pub default_shell: Option<String>,
+    pub backend: Option<String>,
     pub model: Option<String>,
     pub system_prompt: Option<String>,
     pub verbose: Option<bool>,
     pub spinner: Option<bool>,
+    pub stream: Option<bool>,
+    #[serde(default)]
+    pub local: LocalConfig,
+    #[serde(default)]
+    pub openai: OpenAiSettings,
+    #[serde(default)]
+    pub anthropic: AnthropicConfig,
+    #[serde(default)]
+    pub providers: Vec<ProviderEntry>,
+    #[serde(default)]
+    pub safety: PolicyFile,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct AppConfig {
     pub default_shell: Option<String>,
+    pub backend: Option<String>,
     pub model: Option<String>,
     pub system_prompt: Option<String>,
     pub verbose: Option<bool>,
     pub spinner: Option<bool>,
+    pub stream: Option<bool>,
+    pub local: LocalConfig,
+    pub openai: OpenAiSettings,
+    pub anthropic: AnthropicConfig,
+    pub providers: Vec<ProviderEntry>,
+    /// User-level `[[safety.allow]]`, `[[safety.confirm]]` and `[[safety.deny]]` rules.
+    pub safety: PolicyFile,
 }
 
 pub fn load(user_path: Option<PathBuf>) -> Result<AppConfig> {
@@ -55,6 +116,7 @@ pub fn load(user_path: Option<PathBuf>) -> Result<AppConfig> {
     if let Some(path) = user_path.clone() {
         load_from_path(&mut cfg, &path)?;
         if cfg.is_populated() {
+            cfg.apply_env(|key| env::var(key).ok());
             return Ok(cfg);
         }
     }
@@ -63,6 +125,7 @@ pub fn load(user_path: Option<PathBuf>) -> Result<AppConfig> {
         load_from_path(&mut cfg, &default_path)?;
     }
 
+    cfg.apply_env(|key| env::var(key).ok());
     Ok(cfg)
 }
 
@@ -82,6 +145,9 @@ impl AppConfig {
         if self.default_shell.is_none() {
             self.default_shell = file.default_shell;
         }
+        if self.backend.is_none() {
+            self.backend = file.backend;
+        }
         if self.model.is_none() {
             self.model = file.model;
         }
@@ -94,17 +160,121 @@ impl AppConfig {
         if self.spinner.is_none() {
             self.spinner = file.spinner;
         }
+        if self.stream.is_none() {
+            self.stream = file.stream;
+        }
+        if self.local.base_url.is_none() {
+            self.local.base_url = file.local.base_url;
+        }
+        if self.local.model.is_none() {
+            self.local.model = file.local.model;
+        }
+
+        let openai = &mut self.openai;
+        if openai.api_base.is_none() {
+            openai.api_base = file.openai.api_base;
+        }
+        if openai.org_id.is_none() {
+            openai.org_id = file.openai.org_id;
+        }
+        if openai.project.is_none() {
+            openai.project = file.openai.project;
+        }
+        for (name, value) in file.openai.headers {
+            openai.headers.entry(name).or_insert(value);
+        }
+        if openai.azure_deployment.is_none() {
+            openai.azure_deployment = file.openai.azure_deployment;
+        }
+        if openai.azure_api_version.is_none() {
+            openai.azure_api_version = file.openai.azure_api_version;
+        }
+        if openai.structured_output.is_none() {
+            openai.structured_output = file.openai.structured_output;
+        }
+
+        if self.anthropic.api_base.is_none() {
+            self.anthropic.api_base = file.anthropic.api_base;
+        }
+        if self.anthropic.model.is_none() {
+            self.anthropic.model = file.anthropic.model;
+        }
+        if self.providers.is_empty() {
+            self.providers = file.providers;
+        }
+        if self.safety == PolicyFile::default() {
+            self.safety = file.safety;
+        }
+    }
+
+    /// Environment variables take precedence over config files for endpoint settings.
+    fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        let openai = &mut self.openai;
+        if let Some(value) = lookup("OPENAI_BASE_URL") {
+            openai.api_base = Some(value);
+        }
+        if let Some(value) = lookup("OPENAI_ORG_ID") {
+            openai.org_id = Some(value);
+        }
+        if let Some(value) = lookup("OPENAI_PROJECT_ID") {
+            openai.project = Some(value);
+        }
+        if let Some(value) = lookup("TASK_SH_OPENAI_HEADERS") {
+            // Comma-separated `name=value` pairs.
+            for pair in value.split(',') {
+                if let Some((name, value)) = pair.split_once('=') {
+                    openai
+                        .headers
+                        .insert(name.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+        if let Some(value) = lookup("AZURE_OPENAI_DEPLOYMENT") {
+            openai.azure_deployment = Some(value);
+        }
+        if let Some(value) = lookup("AZURE_OPENAI_API_VERSION") {
+            openai.azure_api_version = Some(value);
+        }
+        if let Some(value) = lookup("ANTHROPIC_BASE_URL") {
+            self.anthropic.api_base = Some(value);
+        }
     }
 
     fn is_populated(&self) -> bool {
         self.default_shell.is_some()
+            || self.backend.is_some()
             || self.model.is_some()
             || self.system_prompt.is_some()
             || self.verbose.is_some()
             || self.spinner.is_some()
+            || self.stream.is_some()
+            || self.local.base_url.is_some()
+            || self.local.model.is_some()
+            || self.openai.api_base.is_some()
+            || self.openai.org_id.is_some()
+            || self.openai.project.is_some()
+            || !self.openai.headers.is_empty()
+            || self.openai.azure_deployment.is_some()
+            || self.openai.structured_output.is_some()
+            || self.anthropic.api_base.is_some()
+            || self.anthropic.model.is_some()
+            || !self.providers.is_empty()
+            || self.safety != PolicyFile::default()
     }
 }
 
+/// Directory for state kept between runs, such as provider health.
+///
+/// Defaults to the platform data directory (`~/.local/share/task.sh` on Linux) and can be
+/// overridden with `TASK_SH_DATA_DIR`.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(DATA_DIR_ENV) {
+        return Some(PathBuf::from(dir));
+    }
+
+    dirs::data_local_dir().map(|dir| dir.join("task.sh"))
+}
+
 fn default_path() -> Option<PathBuf> {
     home_dir().map(|mut dir| {
         dir.push(".task.toml");
@@ -123,14 +293,107 @@ mod tests {
         let mut tmp = NamedTempFile::new().unwrap();
         writeln!(
             tmp,
-            "default_shell = \"zsh\"\nmodel = \"gpt-4o-mini\"\nverbose = true\nspinner = false"
+            "default_shell = \"zsh\"\nbackend = \"openai\"\nmodel = \"gpt-4o-mini\"\nverbose = true\nspinner = false\nstream = true"
         )
         .unwrap();
 
         let cfg = load(Some(tmp.path().to_path_buf())).unwrap();
         assert_eq!(cfg.default_shell.as_deref(), Some("zsh"));
+        assert_eq!(cfg.backend.as_deref(), Some("openai"));
         assert_eq!(cfg.model.as_deref(), Some("gpt-4o-mini"));
         assert_eq!(cfg.verbose, Some(true));
         assert_eq!(cfg.spinner, Some(false));
+        assert_eq!(cfg.stream, Some(true));
+    }
+
+    #[test]
+    fn loads_local_backend_table() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        writeln!(
+            tmp,
+            "backend = \"ollama\"\n\n[local]\nbase_url = \"http://buildbox:11434\"\nmodel = \"qwen2.5-coder\""
+        )
+        .unwrap();
+
+        let cfg = load(Some(tmp.path().to_path_buf())).unwrap();
+        assert_eq!(cfg.backend.as_deref(), Some("ollama"));
+        assert_eq!(cfg.local.base_url.as_deref(), Some("http://buildbox:11434"));
+        assert_eq!(cfg.local.model.as_deref(), Some("qwen2.5-coder"));
+    }
+
+    #[test]
+    fn loads_provider_chain_in_order() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        writeln!(
+            tmp,
+            "[[providers]]\nbackend = \"openai\"\nmodel = \"gpt-4o-mini\"\n\n[[providers]]\nbackend = \"ollama\"\nmodel = \"llama3.2\"\n\n[[providers]]\nbackend = \"offline\""
+        )
+        .unwrap();
+
+        let cfg = load(Some(tmp.path().to_path_buf())).unwrap();
+        let chain: Vec<(&str, Option<&str>)> = cfg
+            .providers
+            .iter()
+            .map(|entry| (entry.backend.as_str(), entry.model.as_deref()))
+            .collect();
+        assert_eq!(
+            chain,
+            [
+                ("openai", Some("gpt-4o-mini")),
+                ("ollama", Some("llama3.2")),
+                ("offline", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn loads_safety_rules() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        writeln!(
+            tmp,
+            "[[safety.deny]]\ncommand = \"kubectl\"\nargs = [\"delete\"]\n\n[[safety.allow]]\ncommand = \"sudo\"\nargs = [\"apt\"]"
+        )
+        .unwrap();
+
+        let cfg = load(Some(tmp.path().to_path_buf())).unwrap();
+        assert_eq!(cfg.safety.deny[0].command, "kubectl");
+        assert_eq!(cfg.safety.deny[0].args, ["delete"]);
+        assert_eq!(cfg.safety.allow[0].command, "sudo");
+        assert!(cfg.safety.confirm.is_empty());
+    }
+
+    #[test]
+    fn environment_overrides_openai_settings() {
+        let mut tmp = NamedTempFile::new().unwrap();
+        writeln!(
+            tmp,
+            "[openai]\napi_base = \"https://gateway.internal/v1\"\norg_id = \"org-file\"\nheaders = {{ \"X-Team\" = \"infra\" }}"
+        )
+        .unwrap();
+
+        let mut cfg = AppConfig::default();
+        load_from_path(&mut cfg, &tmp.path().to_path_buf()).unwrap();
+        cfg.apply_env(|key| match key {
+            "OPENAI_ORG_ID" => Some("org-env".to_string()),
+            "OPENAI_PROJECT_ID" => Some("proj_123".to_string()),
+            "TASK_SH_OPENAI_HEADERS" => Some("X-Trace=on, X-Team=platform".to_string()),
+            _ => None,
+        });
+
+        let openai = &cfg.openai;
+        assert_eq!(
+            openai.api_base.as_deref(),
+            Some("https://gateway.internal/v1")
+        );
+        assert_eq!(openai.org_id.as_deref(), Some("org-env"));
+        assert_eq!(openai.project.as_deref(), Some("proj_123"));
+        assert_eq!(
+            openai.headers.get("X-Team").map(String::as_str),
+            Some("platform")
+        );
+        assert_eq!(
+            openai.headers.get("X-Trace").map(String::as_str),
+            Some("on")
+        );
     }
 }
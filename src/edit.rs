@@ -0,0 +1,88 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+/// Let the user change `command` before it runs.
+///
+/// Single-line commands are edited in place, prefilled with the suggestion; multi-line ones open
+/// in `$VISUAL` or `$EDITOR`. `None` means the edit was abandoned with Ctrl-C or Ctrl-D.
+pub fn edit_command(command: &str) -> Result<Option<String>> {
+    if command.contains('\n') {
+        return in_editor(command, &editor()).map(Some);
+    }
+    if !atty::is(atty::Stream::Stdin) {
+        return read_replacement();
+    }
+
+    let mut line = DefaultEditor::new().context("Failed to start the line editor")?;
+    match line.readline_with_initial("> ", (command, "")) {
+        Ok(edited) => Ok(Some(edited)),
+        Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(None),
+        Err(err) => Err(err).context("Failed to read the edited command"),
+    }
+}
+
+/// Without a terminal there is nothing to edit in, so the next line replaces the command.
+fn read_replacement() -> Result<Option<String>> {
+    print!("> ");
+    io::stdout().flush().context("Failed to flush stdout")?;
+    let mut line = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read the edited command")?;
+    Ok((read > 0).then(|| line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// The user's editor, which may carry its own arguments such as `code --wait`.
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// The file is created exclusively under a random name, readable by the user alone, and removed
+/// when the handle drops. Editors often save by replacing the file, so it is read back by path.
+fn in_editor(command: &str, editor: &str) -> Result<String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("task-sh-edit-")
+        .suffix(".sh")
+        .tempfile()
+        .context("Failed to create a file for editing")?;
+    writeln!(file, "{command}")
+        .and_then(|()| file.flush())
+        .context("Failed to write the command for editing")?;
+
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(file.path()).status();
+    let edited = fs::read_to_string(file.path());
+
+    let status = status.with_context(|| format!("Failed to start editor '{program}'"))?;
+    if !status.success() {
+        bail!("Editor '{program}' exited with {status}");
+    }
+    let edited = edited.context("Failed to read the edited command")?;
+    Ok(edited.trim_end().to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_line_commands_round_trip_through_the_editor() {
+        let command = "for f in *.log; do\n  gzip \"$f\"\ndone";
+        let edited = in_editor(command, "sed -i s/gzip/xz/").unwrap();
+        assert_eq!(edited, "for f in *.log; do\n  xz \"$f\"\ndone");
+
+        assert!(in_editor(command, "false").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{self, Backend, BackendError};
+
+/// Cooldown after the first consecutive failure; doubles with each further failure.
+const BASE_COOLDOWN_SECS: i64 = 30;
+/// Upper bound for the cooldown of a repeatedly failing provider.
+const MAX_COOLDOWN_SECS: i64 = 15 * 60;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct HealthEntry {
+    consecutive_failures: u32,
+    last_failure: Option<i64>,
+    last_error: Option<String>,
+    last_success: Option<i64>,
+}
+
+impl HealthEntry {
+    fn cooling_down(&self, now: i64) -> bool {
+        let Some(last_failure) = self.last_failure else {
+            return false;
+        };
+        if self.consecutive_failures == 0 {
+            return false;
+        }
+
+        let exponent = self.consecutive_failures.saturating_sub(1).min(10);
+        let cooldown = (BASE_COOLDOWN_SECS << exponent).min(MAX_COOLDOWN_SECS);
+        now - last_failure < cooldown
+    }
+}
+
+/// Per-provider success and failure history, persisted between runs.
+///
+/// Providers that failed recently are tried after healthy ones, so a rate-limited primary does
+/// not cost a round of retries on every invocation.
+#[derive(Debug, Default)]
+pub struct ProviderHealth {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, HealthEntry>,
+}
+
+impl ProviderHealth {
+    /// Load health state from `path`; a missing or unreadable file starts from a clean slate.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Track health for this run only, without reading or writing state.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Order providers for this attempt: healthy ones first, then those cooling down, each group
+    /// keeping its configured order.
+    pub fn order<'a>(&self, providers: &'a [Box<dyn Backend>]) -> Vec<&'a dyn Backend> {
+        let now = Utc::now().timestamp();
+        let (healthy, cooling): (Vec<&dyn Backend>, Vec<&dyn Backend>) = providers
+            .iter()
+            .map(|provider| provider.as_ref())
+            .partition(|provider| {
+                self.entries
+                    .get(&backend::label(*provider))
+                    .is_none_or(|entry| !entry.cooling_down(now))
+            });
+
+        healthy.into_iter().chain(cooling).collect()
+    }
+
+    pub fn record_success(&mut self, provider: &dyn Backend) {
+        let entry = self.entries.entry(backend::label(provider)).or_default();
+        entry.consecutive_failures = 0;
+        entry.last_success = Some(Utc::now().timestamp());
+    }
+
+    pub fn record_failure(&mut self, provider: &dyn Backend, err: &BackendError) {
+        let entry = self.entries.entry(backend::label(provider)).or_default();
+        entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+        entry.last_failure = Some(Utc::now().timestamp());
+        entry.last_error = Some(err.to_string());
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = toml::to_string(&self.entries).context("Failed to encode health state")?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::OfflineBackend;
+    use tempfile::tempdir;
+
+    #[test]
+    fn failing_providers_move_to_the_back() {
+        let providers: Vec<Box<dyn Backend>> = vec![
+            Box::new(crate::backend::OpenAiBackend::new(
+                "gpt-4o-mini".to_string(),
+                Default::default(),
+            )),
+            Box::new(OfflineBackend),
+        ];
+        let mut health = ProviderHealth::in_memory();
+
+        health.record_failure(
+            providers[0].as_ref(),
+            &BackendError::RateLimited("slow down".to_string()),
+        );
+        let order: Vec<String> = health
+            .order(&providers)
+            .into_iter()
+            .map(backend::label)
+            .collect();
+        assert_eq!(order, ["offline:rules", "openai:gpt-4o-mini"]);
+
+        health.record_success(providers[0].as_ref());
+        assert_eq!(
+            backend::label(health.order(&providers)[0]),
+            "openai:gpt-4o-mini"
+        );
+    }
+
+    #[test]
+    fn persists_between_runs() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("health.toml");
+
+        let mut health = ProviderHealth::load(path.clone());
+        health.record_failure(&OfflineBackend, &BackendError::Timeout);
+        health.save().unwrap();
+
+        let reloaded = ProviderHealth::load(path);
+        let entry = &reloaded.entries["offline:rules"];
+        assert_eq!(entry.consecutive_failures, 1);
+        assert_eq!(entry.last_error.as_deref(), Some("Request timed out"));
+    }
+}
@@ -0,0 +1,14 @@
+# task-sh: replace the prompt line with the command generated from it.
+# Load with `eval "$(task init bash)"`; Ctrl-G runs the widget, rebind with `bind -x`.
+
+_task_sh_widget() {
+  [[ -z ${READLINE_LINE//[[:space:]]/} ]] && return
+
+  local command
+  if command=$(command task gen --emit-only --shell bash -- "$READLINE_LINE" </dev/null); then
+    READLINE_LINE=$command
+    READLINE_POINT=${#READLINE_LINE}
+  fi
+}
+
+bind -x '"\C-g": _task_sh_widget'
@@ -0,0 +1,14 @@
+# task-sh: replace the prompt line with the command generated from it.
+# Load with `task init fish | source`; Ctrl-G runs the function, rebind with `bind`.
+
+function _task_sh_widget
+    set -l description (commandline | string collect)
+    string trim -- $description | string length -q; or return
+
+    set -l command (command task gen --emit-only --shell fish -- $description </dev/null | string collect)
+    and commandline --replace -- $command
+    commandline -f repaint
+end
+
+bind \cg _task_sh_widget
+bind -M insert \cg _task_sh_widget 2>/dev/null
@@ -1,11 +1,22 @@
+mod backend;
 mod config;
+mod edit;
 mod generator;
+mod health;
+mod policy;
+mod pty;
+mod redact;
+mod safety;
+mod sandbox;
+mod supervisor;
+mod undo;
 
 use std::collections::HashSet;
+use std::fmt;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitCode, ExitStatus, Stdio};
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
@@ -23,8 +34,17 @@ use rand::{seq::SliceRandom, thread_rng};
 use rpassword::read_password;
 use tracing::{info, warn};
 
-use crate::config::{load as load_config, save_default_env};
-use crate::generator::{CommandConfidence, GeneratedCommand, generate_command};
+use crate::backend::{Backend, BackendError, BackendKind, TokenSink};
+use crate::config::{AppConfig, load as load_config, save_default_env};
+use crate::generator::{
+    Alternative, Blocked, CommandConfidence, GeneratedCommand, enforce_safety, generate_command,
+};
+use crate::health::ProviderHealth;
+use crate::policy::{Policy, SafetyReport, Tier};
+use crate::redact::Redaction;
+use crate::safety::RiskLevel;
+use crate::supervisor::{Finished, Supervisor};
+use crate::undo::UndoStore;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -33,7 +53,7 @@ use crate::generator::{CommandConfidence, GeneratedCommand, generate_command};
     version,
     about = "Generate safe shell commands from natural language prompts",
     long_about = "task is a CLI assistant that converts natural language descriptions into shell commands using OpenAI-backed intelligence.",
-    after_help = "EXAMPLES:\n  task gen \"list large files\" --shell zsh -v\n  echo \"list staged changes\" | task gen --verbose\n\nCONFIG:\n  ~/.task.toml    Default configuration file.\n  --config         Override configuration path.\n\nENVIRONMENT:\n  OPENAI_API_KEY           Required for live command generation\n  TASK_SH_FAKE_RESPONSE    Optional testing override.",
+    after_help = "EXAMPLES:\n  task gen \"list large files\" --shell zsh -v\n  echo \"list staged changes\" | task gen --verbose\n\nCONFIG:\n  ~/.task.toml    Default configuration file.\n  --config         Override configuration path.\n\nENVIRONMENT:\n  OPENAI_API_KEY           Required for live command generation\n  ANTHROPIC_API_KEY        Required with --backend anthropic\n  TASK_SH_FAKE_RESPONSE    Optional testing override.",
     propagate_version = true
 )]
 struct Cli {
@@ -64,13 +84,45 @@ enum Commands {
         #[arg(long, value_name = "PROMPT")]
         system_prompt: Option<String>,
 
-        /// Override the model name used for generation
+        /// Model provider used for generation, bypassing any configured fallback chain
+        #[arg(long)]
+        backend: Option<BackendKind>,
+
+        /// Override the model name used for generation (single-backend mode only)
         #[arg(long, value_name = "MODEL")]
         model: Option<String>,
 
+        /// Print the result as JSON instead of offering to run it
+        #[arg(long, action = ArgAction::SetTrue)]
+        json: bool,
+
+        /// Print only the command, for shell widgets that put it in the prompt; nothing is run
+        #[arg(long, action = ArgAction::SetTrue, conflicts_with = "json")]
+        emit_only: bool,
+
         /// Disable progress spinner even if enabled in config
         #[arg(long, action = ArgAction::SetFalse)]
         spinner: Option<bool>,
+
+        /// Show the model's answer as it is generated
+        #[arg(long, action = ArgAction::SetTrue)]
+        stream: bool,
+
+        /// Run the command in a sandbox first and report the files it would change
+        #[arg(long, action = ArgAction::SetTrue)]
+        dry_run: bool,
+
+        /// List the secrets and personal data masked before the description was sent
+        #[arg(long, action = ArgAction::SetTrue)]
+        show_redactions: bool,
+
+        /// Run the command on a pseudo-terminal, as interactive programs like vim or htop need
+        #[arg(long, action = ArgAction::SetTrue)]
+        tty: bool,
+
+        /// Stop the command if it runs longer than this, e.g. 90s, 5m or 1h
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        exec_timeout: Option<Duration>,
     },
 
     /// Generate shell autocompletion scripts
@@ -79,12 +131,47 @@ enum Commands {
         #[arg(value_enum)]
         shell: Shell,
     },
+
+    /// Print a key binding that replaces the prompt line with the command generated from it
+    Init {
+        /// Shell to print the key binding for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Restore the files changed by an executed command
+    Undo {
+        /// Snapshot to restore; defaults to the most recent one
+        id: Option<String>,
+
+        /// List stored snapshots instead of restoring one
+        #[arg(long, action = ArgAction::SetTrue)]
+        list: bool,
+    },
+
+    /// Inspect the safety policy
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PolicyCommand {
+    /// Show how the safety policy treats a command, and which rules decide it
+    Check {
+        /// Shell command to check
+        command: String,
+    },
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum Shell {
     Bash,
     Zsh,
+    Fish,
+    Sh,
+    Nu,
 }
 
 impl Shell {
@@ -92,72 +179,217 @@ impl Shell {
         match self {
             Shell::Bash => "bash",
             Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Sh => "sh",
+            Shell::Nu => "nu",
         }
     }
 }
 
+/// Exit codes for outcomes of `task` itself; when a command runs, its own status is passed through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    Failure = 1,
+    /// No usable command came back from the providers.
+    GenerationFailed = 80,
+    /// A provider rejected the credentials, or none were configured.
+    AuthFailed = 81,
+    /// The safety policy refused the command.
+    Blocked = 82,
+    /// The command was not confirmed, so it did not run.
+    Declined = 83,
+    /// There was no terminal to confirm or run the command on, so it did not run.
+    NotInteractive = 84,
+    /// The command ran past `--exec-timeout` and was stopped, as with coreutils `timeout`.
+    TimedOut = 124,
+}
+
+impl Exit {
+    fn for_error(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<Blocked>().is_some() {
+            Exit::Blocked
+        } else if matches!(
+            err.downcast_ref::<BackendError>(),
+            Some(BackendError::Auth(_))
+        ) {
+            Exit::AuthFailed
+        } else if err.downcast_ref::<GenerationFailed>().is_some() {
+            Exit::GenerationFailed
+        } else {
+            Exit::Failure
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+/// Context for errors from [`generate_command`], which exit with [`Exit::GenerationFailed`].
+#[derive(Debug)]
+struct GenerationFailed(String);
+
+impl fmt::Display for GenerationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to generate command for description: {}", self.0)
+    }
+}
+
+/// Parse a duration such as `90`, `90s`, `5m` or `1h`; bare numbers are seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|ch: char| !ch.is_ascii_digit() && ch != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{value}'; use e.g. 90s, 5m or 1h"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        other => return Err(format!("unknown unit '{other}'; use ms, s, m or h")),
+    };
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| format!("duration '{value}' must be positive"))
+}
+
+/// The exit code for a finished command: its own code, or 128 plus the signal that ended it.
+fn status_code(status: ExitStatus) -> ExitCode {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return ExitCode::from(128u8.wrapping_add(signal as u8));
+    }
+    match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => Exit::Failure.into(),
+    }
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     dotenvy::dotenv().ok();
     init_tracing();
 
     let cli = Cli::parse();
 
-    if needs_api_key(&cli) {
-        ensure_required_env()?;
+    match run(cli).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", format!("Error: {:#}", err).red());
+            Exit::for_error(&err).into()
+        }
     }
+}
 
+async fn run(cli: Cli) -> Result<ExitCode> {
     let config_path = cli.config.as_ref().map(|p| p.into());
     let app_config = load_config(config_path)?;
 
-    let result = match cli.command {
+    match cli.command {
         Commands::Gen {
             description,
             shell,
             verbose,
             system_prompt,
+            backend,
             model,
+            json,
+            emit_only,
             spinner,
+            stream,
+            dry_run,
+            show_redactions,
+            tty,
+            exec_timeout,
         } => {
             let effective_verbose = verbose || app_config.verbose.unwrap_or(false);
-            handle_generate(
+            let providers = resolve_providers(backend, model.as_deref(), &app_config)?;
+            let mut health = if providers.len() > 1 {
+                config::data_dir()
+                    .map(|dir| ProviderHealth::load(dir.join("health.toml")))
+                    .unwrap_or_else(ProviderHealth::in_memory)
+            } else {
+                ensure_required_env(providers[0].as_ref(), emit_only)?;
+                ProviderHealth::in_memory()
+            };
+            let policy = Policy::load(&app_config.safety)?;
+            let options = GenerateOptions {
                 description,
-                shell.or_else(|| {
-                    app_config
-                        .default_shell
-                        .as_deref()
-                        .and_then(Shell::from_str_case_insensitive)
-                }),
-                effective_verbose,
-                system_prompt.or(app_config.system_prompt.clone()),
-                model.or(app_config.model.clone()),
-                spinner.unwrap_or_else(|| app_config.spinner.unwrap_or(true)),
-            )
-            .await
-        }
-        Commands::Completions { shell } => {
-            generate_completions(shell);
-            Ok(())
-        }
-    };
-
-    match result {
-        Ok(()) => Ok(()),
-        Err(err) => {
-            eprintln!("{}", format!("Error: {:#}", err).red());
-            Err(err)
+                shell: resolve_shell(shell, &app_config),
+                verbose: effective_verbose,
+                system_prompt: system_prompt.or(app_config.system_prompt.clone()),
+                json,
+                emit_only,
+                // A widget is waiting on the output, and the spinner would draw over its prompt.
+                spinner: !emit_only
+                    && spinner.unwrap_or_else(|| app_config.spinner.unwrap_or(true)),
+                stream: stream || app_config.stream.unwrap_or(false),
+                dry_run,
+                show_redactions,
+                tty,
+                exec_timeout,
+            };
+            handle_generate(&providers, &mut health, &policy, options).await
         }
+        Commands::Completions { shell } => generate_completions(shell).map(|()| ExitCode::SUCCESS),
+        Commands::Init { shell } => init_script(shell).map(|script| {
+            print!("{script}");
+            ExitCode::SUCCESS
+        }),
+        Commands::Policy {
+            command: PolicyCommand::Check { command },
+        } => Policy::load(&app_config.safety).map(|policy| {
+            print_policy_check(&policy, &command);
+            ExitCode::SUCCESS
+        }),
+        Commands::Undo { id, list } => handle_undo(id.as_deref(), list).map(|()| ExitCode::SUCCESS),
     }
 }
 
-async fn handle_generate(
+/// Flags for a single `task gen` run, already merged with config defaults.
+struct GenerateOptions {
     description: Option<String>,
-    shell: Option<Shell>,
+    shell: (Shell, ShellSource),
     verbose: bool,
     system_prompt: Option<String>,
-    model: Option<String>,
-    spinner_enabled: bool,
-) -> Result<()> {
+    json: bool,
+    emit_only: bool,
+    spinner: bool,
+    stream: bool,
+    dry_run: bool,
+    show_redactions: bool,
+    tty: bool,
+    exec_timeout: Option<Duration>,
+}
+
+async fn handle_generate(
+    providers: &[Box<dyn Backend>],
+    health: &mut ProviderHealth,
+    policy: &Policy,
+    options: GenerateOptions,
+) -> Result<ExitCode> {
+    let GenerateOptions {
+        description,
+        shell,
+        verbose,
+        system_prompt,
+        json,
+        emit_only,
+        spinner: spinner_enabled,
+        stream,
+        dry_run,
+        show_redactions,
+        tty,
+        exec_timeout,
+    } = options;
+
     let prompt = match description {
         Some(desc) if !desc.trim().is_empty() => desc,
         Some(_) | None => {
@@ -175,7 +407,7 @@ async fn handle_generate(
         }
     };
 
-    let shell = shell.unwrap_or(Shell::Bash);
+    let (shell, shell_source) = shell;
 
     let spinner = if spinner_enabled {
         let pb = ProgressBar::new_spinner();
@@ -191,25 +423,129 @@ async fn handle_generate(
         None
     };
 
+    // Streamed text is a preview on stderr; the command shown below is the parsed, checked one.
+    let mut streamed = false;
+    let mut preview = |token: &str| {
+        if !streamed {
+            if let Some(pb) = &spinner {
+                pb.finish_and_clear();
+            }
+            streamed = true;
+        }
+        eprint!("{}", token.bright_black());
+        let _ = io::stderr().flush();
+    };
+    let on_token: Option<&mut TokenSink> = if stream { Some(&mut preview) } else { None };
+
+    let generated = generate_command(
+        providers,
+        health,
+        policy,
+        prompt.trim(),
+        shell.as_str(),
+        system_prompt.as_deref(),
+        on_token,
+    )
+    .await;
+
+    if streamed {
+        eprintln!();
+    }
+
+    if let Err(err) = health.save() {
+        warn!(error = %err, "Failed to save provider health");
+    }
+
     let GeneratedCommand {
         cmd,
         explanation,
         raw_response,
         confidence,
         alternatives,
-    } = generate_command(
-        prompt.trim(),
-        shell.as_str(),
-        system_prompt.as_deref(),
-        model.as_deref(),
-    )
-    .await
-    .with_context(|| format!("Failed to generate command for description: {prompt}"))?;
+        provider,
+        requires_sudo,
+        destructive,
+        safety,
+        redactions,
+    } = generated.with_context(|| GenerationFailed(prompt.clone()))?;
 
     if let Some(pb) = spinner {
         pb.finish_and_clear();
     }
 
+    if json {
+        let confidence = match confidence {
+            CommandConfidence::Certain => "certain",
+            CommandConfidence::NeedsConfirmation => "needs_confirmation",
+        };
+        let (blocked, allowed): (Vec<&Alternative>, Vec<&Alternative>) = alternatives
+            .iter()
+            .partition(|alt| alt.safety.tier == Tier::Deny);
+        let allowed: Vec<&str> = allowed.iter().map(|alt| alt.command.as_str()).collect();
+        let blocked: Vec<serde_json::Value> = blocked
+            .iter()
+            .map(|alt| {
+                serde_json::json!({
+                    "command": alt.command,
+                    "reason": alt.safety.decisive().next().map(|finding| &finding.reason),
+                })
+            })
+            .collect();
+        let mut output = serde_json::json!({
+            "shell": shell.as_str(),
+            "command": cmd,
+            "explanation": explanation,
+            "alternatives": allowed,
+            "blocked_alternatives": blocked,
+            "confidence": confidence,
+            "requires_sudo": requires_sudo,
+            "destructive": destructive,
+            "risk": safety.level.to_string(),
+            "affected_paths": safety.paths,
+            "provider": provider,
+            "raw_response": raw_response,
+        });
+        if show_redactions {
+            output["redactions"] = redactions
+                .iter()
+                .map(|redaction| {
+                    serde_json::json!({
+                        "kind": redaction.kind.to_string(),
+                        "placeholder": redaction.placeholder,
+                        "preview": redaction.preview(),
+                    })
+                })
+                .collect();
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if emit_only {
+        let Some(command) = executable_command(&cmd) else {
+            return Err(anyhow!("No runnable command was produced"))
+                .with_context(|| GenerationFailed(prompt.clone()));
+        };
+        // The prompt line is the only confirmation left, which is not enough for these.
+        if needs_typed_confirmation(&safety) {
+            eprintln!(
+                "{}",
+                format!(
+                    "Not emitting `{command}`, which needs confirmation: {} Run `task gen` without --emit-only to review it.",
+                    safety.explanation()
+                )
+                .yellow()
+            );
+            return Ok(Exit::Blocked.into());
+        }
+        println!("{command}");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if show_redactions {
+        print_redactions(&redactions);
+    }
+
     println!(
         "{}",
         format!("Suggested command ({}):", shell.as_str()).green()
@@ -222,6 +558,19 @@ async fn handle_generate(
     };
     println!("{}", cmd_output);
 
+    if destructive {
+        println!(
+            "{}",
+            "Warning: this command may delete or overwrite data.".bright_red()
+        );
+    }
+    if requires_sudo {
+        println!(
+            "{}",
+            "Note: this command needs elevated privileges.".yellow()
+        );
+    }
+
     if verbose {
         if let Some(raw) = raw_response {
             println!("\n{}", "Raw response:".yellow());
@@ -230,19 +579,32 @@ async fn handle_generate(
 
         println!("\n{}", "Explanation:".green());
         println!("{}", explanation.green());
+
+        if let Some(provider) = &provider {
+            println!("\n{} {}", "Answered by:".green(), provider);
+        }
+
+        println!(
+            "{} {} ({})",
+            "Target shell:".green(),
+            shell.as_str(),
+            shell_source
+        );
     }
 
     let mut seen_commands: HashSet<String> = HashSet::new();
-    let mut command_options: Vec<String> = Vec::new();
+    let mut command_options: Vec<(String, SafetyReport)> = Vec::new();
 
     if let Some(primary_cmd) =
         executable_command(&cmd).filter(|pc| seen_commands.insert(pc.clone()))
     {
-        command_options.push(primary_cmd);
+        command_options.push((primary_cmd, safety));
     }
 
-    command_options.extend(alternatives.iter().filter_map(|alt| {
-        executable_command(alt).filter(|cmd_option| seen_commands.insert(cmd_option.clone()))
+    command_options.extend(alternatives.into_iter().filter_map(|alt| {
+        executable_command(&alt.command)
+            .filter(|cmd_option| seen_commands.insert(cmd_option.clone()))
+            .map(|cmd_option| (cmd_option, alt.safety))
     }));
 
     if command_options.is_empty() {
@@ -256,35 +618,50 @@ async fn handle_generate(
             "{}",
             "No runnable commands were produced. The request may be unclear—try adding more detail.".yellow()
         );
-        return Ok(());
+        return Ok(Exit::GenerationFailed.into());
     }
 
     if command_options.len() > 1 {
         println!("\n{}", "Command options:".yellow());
-        for (idx, option) in command_options.iter().enumerate() {
-            println!("  {}. {}", idx + 1, option);
+        for (idx, (option, report)) in command_options.iter().enumerate() {
+            println!("  {}. {}{}", idx + 1, option, safety_note(report));
         }
         println!(
             "{}",
             "Multiple possible commands detected. Choose one to run:".bright_yellow()
         );
-        if let Some(choice) = prompt_for_command_selection(&command_options)? {
-            confirm_and_execute(&choice, shell.as_str())?;
+        if let Some((choice, report)) = prompt_for_command_selection(&command_options)? {
+            confirm_and_execute(
+                policy,
+                &choice,
+                &report,
+                shell.as_str(),
+                dry_run,
+                tty,
+                exec_timeout,
+            )
         } else {
             println!("{}", "No command selected; exiting.".yellow());
+            Ok(Exit::Declined.into())
         }
     } else {
-        let primary_cmd = &command_options[0];
+        let (primary_cmd, report) = &command_options[0];
         if matches!(confidence, CommandConfidence::NeedsConfirmation) {
             println!(
                 "{}",
                 "AI is unsure about this command; review carefully before running.".bright_yellow()
             );
         }
-        confirm_and_execute(primary_cmd, shell.as_str())?;
+        confirm_and_execute(
+            policy,
+            primary_cmd,
+            report,
+            shell.as_str(),
+            dry_run,
+            tty,
+            exec_timeout,
+        )
     }
-
-    Ok(())
 }
 
 fn read_stdin() -> Result<Option<String>> {
@@ -313,43 +690,147 @@ fn init_tracing() {
     let _ = tracing::subscriber::set_global_default(subscriber);
 }
 
-fn needs_api_key(cli: &Cli) -> bool {
-    matches!(cli.command, Commands::Gen { .. })
+/// Where the target shell for a `task gen` run came from.
+#[derive(Copy, Clone, Debug)]
+enum ShellSource {
+    Flag,
+    Config,
+    ParentProcess,
+    ShellEnv,
+    Default,
+}
+
+impl fmt::Display for ShellSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShellSource::Flag => "from --shell",
+            ShellSource::Config => "from default_shell in config",
+            ShellSource::ParentProcess => "detected from parent process",
+            ShellSource::ShellEnv => "detected from $SHELL",
+            ShellSource::Default => "default",
+        })
+    }
+}
+
+/// Pick the target shell: `--shell`, then `default_shell` from config, then the shell that
+/// launched `task`, then `$SHELL`, falling back to bash.
+fn resolve_shell(flag: Option<Shell>, config: &AppConfig) -> (Shell, ShellSource) {
+    if let Some(shell) = flag {
+        return (shell, ShellSource::Flag);
+    }
+    if let Some(shell) = config
+        .default_shell
+        .as_deref()
+        .and_then(Shell::from_str_case_insensitive)
+    {
+        return (shell, ShellSource::Config);
+    }
+    if let Some(shell) = parent_process_shell() {
+        return (shell, ShellSource::ParentProcess);
+    }
+    if let Some(shell) =
+        std::env::var_os("SHELL").and_then(|path| shell_from_path(Path::new(&path)))
+    {
+        return (shell, ShellSource::ShellEnv);
+    }
+
+    (Shell::Bash, ShellSource::Default)
+}
+
+/// The shell `task` was started from, read from `/proc/<ppid>/exe`.
+#[cfg(target_os = "linux")]
+fn parent_process_shell() -> Option<Shell> {
+    let ppid = std::os::unix::process::parent_id();
+    let exe = fs::read_link(format!("/proc/{ppid}/exe")).ok()?;
+    shell_from_path(&exe)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn parent_process_shell() -> Option<Shell> {
+    None
 }
 
-fn ensure_required_env() -> Result<()> {
-    const VAR: &str = "OPENAI_API_KEY";
+fn shell_from_path(path: &Path) -> Option<Shell> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(Shell::from_str_case_insensitive)
+}
+
+/// Build the providers to try, in order.
+///
+/// `--backend` or `--model` select a single provider; otherwise the configured `[[providers]]`
+/// chain is used, falling back to the single `backend` setting.
+fn resolve_providers(
+    flag: Option<BackendKind>,
+    model: Option<&str>,
+    config: &AppConfig,
+) -> Result<Vec<Box<dyn Backend>>> {
+    if flag.is_none() && model.is_none() && !config.providers.is_empty() {
+        return config
+            .providers
+            .iter()
+            .map(|entry| {
+                let kind = BackendKind::from_str_case_insensitive(&entry.backend)
+                    .ok_or_else(|| anyhow!("Unknown backend '{}' in providers", entry.backend))?;
+                Ok(backend::build(kind, config, entry.model.as_deref()))
+            })
+            .collect();
+    }
+
+    let kind = match (flag, config.backend.as_deref()) {
+        (Some(kind), _) => kind,
+        (None, Some(name)) => BackendKind::from_str_case_insensitive(name)
+            .ok_or_else(|| anyhow!("Unknown backend '{name}' in config"))?,
+        (None, None) => BackendKind::Openai,
+    };
+
+    Ok(vec![backend::build(kind, config, model)])
+}
+
+/// Make sure the backend's API key is set, asking for it on a terminal unless `emit_only`, where
+/// stdout belongs to the widget.
+fn ensure_required_env(backend: &dyn Backend, emit_only: bool) -> Result<()> {
     const FAKE_VAR: &str = "TASK_SH_FAKE_RESPONSE";
 
-    if matches!(std::env::var(VAR), Ok(ref v) if !v.trim().is_empty()) {
+    let Some(var) = backend.capabilities().credential_env else {
+        return Ok(());
+    };
+
+    if matches!(std::env::var(var), Ok(ref v) if !v.trim().is_empty()) {
         return Ok(());
     }
 
     if let Ok(_fake) = std::env::var(FAKE_VAR) {
         unsafe {
-            std::env::set_var(VAR, "sk-test-placeholder");
+            std::env::set_var(var, "sk-test-placeholder");
         }
-        println!(
+        eprintln!(
             "{}",
             format!("Using {} for deterministic output.", FAKE_VAR).bright_black()
         );
         return Ok(());
     }
 
-    if !atty::is(Stream::Stdin) {
-        return Err(anyhow!(
-            "OPENAI_API_KEY is not set. Provide it via environment, .env, or use TASK_SH_FAKE_RESPONSE for testing."
-        ));
+    if emit_only || !atty::is(Stream::Stdin) {
+        return Err(anyhow!(BackendError::Auth(format!(
+            "{var} is not set. Provide it via environment, .env, or use TASK_SH_FAKE_RESPONSE for testing."
+        ))));
     }
 
     println!(
         "{}",
-        "task.sh hasn’t been connected to OpenAI yet. Let’s add your API key.".cyan()
-    );
-    println!(
-        "{}",
-        "You can generate one at https://platform.openai.com/api-keys".bright_black()
+        format!(
+            "task.sh hasn’t been connected to {} yet. Let’s add your API key.",
+            backend.name()
+        )
+        .cyan()
     );
+    if let Some(url) = api_key_url(backend.name()) {
+        println!(
+            "{}",
+            format!("You can generate one at {url}").bright_black()
+        );
+    }
 
     let key = prompt_for_api_key()?;
     let trimmed = key.trim();
@@ -361,14 +842,22 @@ fn ensure_required_env() -> Result<()> {
         std::process::exit(1);
     }
 
-    save_default_env(VAR, trimmed)?;
+    save_default_env(var, trimmed)?;
     unsafe {
-        std::env::set_var(VAR, trimmed);
+        std::env::set_var(var, trimmed);
     }
     println!("{}", "API key saved to .env".green());
     Ok(())
 }
 
+fn api_key_url(backend: &str) -> Option<&'static str> {
+    match backend {
+        "openai" => Some("https://platform.openai.com/api-keys"),
+        "anthropic" => Some("https://console.anthropic.com/settings/keys"),
+        _ => None,
+    }
+}
+
 fn prompt_for_api_key() -> Result<String> {
     print!("{}", "API key: ".bright_blue());
     io::stdout().flush().ok();
@@ -376,62 +865,362 @@ fn prompt_for_api_key() -> Result<String> {
     Ok(key)
 }
 
-fn maybe_execute(command: &str, shell: &str, _force_interactive: bool) -> Result<()> {
+/// Whether a command runs for real, on a pseudo-terminal, or in the throwaway dry-run sandbox.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Execution {
+    Real,
+    Terminal,
+    DryRun,
+}
+
+fn maybe_execute(
+    command: &str,
+    report: &SafetyReport,
+    shell: &str,
+    execution: Execution,
+    timeout: Option<Duration>,
+) -> Result<Option<Finished>> {
     if command.trim().is_empty() {
-        return Ok(());
+        return Ok(None);
+    }
+
+    if execution != Execution::DryRun {
+        snapshot_before_run(command, report);
+    }
+
+    let (finished, changes) = match execution {
+        // The command owns the screen, so there is no animation and nothing to enrich.
+        Execution::Terminal => {
+            println!();
+            (pty::run(command, shell, timeout)?, None)
+        }
+        Execution::Real => {
+            let stop_animation = start_animation(command);
+            let captured = run_streaming(command, shell, timeout, stop_animation)?;
+            finish_line(&captured.stdout, &captured.stderr);
+            (captured.finished, None)
+        }
+        Execution::DryRun => {
+            let stop_animation = start_animation(command);
+            let result = sandbox::dry_run(command, shell, timeout);
+            stop_animation();
+            let run = result?;
+            if !run.stdout.is_empty() {
+                io::stdout().write_all(enrich_find_output(command, &run.stdout).as_bytes())?;
+            }
+            io::stderr().write_all(&run.stderr)?;
+            finish_line(&run.stdout, &run.stderr);
+            let finished = Finished {
+                status: run.status,
+                timed_out: run.timed_out,
+            };
+            (finished, Some(run.changes))
+        }
+    };
+
+    match changes {
+        Some(changes) => print_dry_run_changes(&changes, finished, timeout),
+        None => print_finished(finished, timeout),
+    }
+
+    Ok(Some(finished))
+}
+
+/// Animate until the returned closure runs, which clears the animation and leaves a blank line
+/// before the command's first output.
+fn start_animation(command: &str) -> impl FnOnce() + Send + use<> {
+    let animation = Animation::start(command);
+    move || {
+        drop(animation);
+        println!();
     }
+}
 
-    if !atty::is(atty::Stream::Stdin) {
+fn print_finished(finished: Finished, timeout: Option<Duration>) {
+    let status = finished.status;
+    if finished.timed_out {
+        let limit = timeout
+            .map(|timeout| format!(" after {timeout:?}"))
+            .unwrap_or_default();
         println!(
             "{}",
-            "Non-interactive session detected; skipping execution.".yellow()
+            format!("Command timed out{limit} and was stopped ({status}).").red()
+        );
+    } else if status.success() {
+        println!("{}", "Command completed successfully.".green());
+    } else {
+        println!(
+            "{}",
+            format!("Command exited with status: {}", status).red()
         );
-        return Ok(());
     }
+}
 
-    let is_running = Arc::new(AtomicBool::new(true));
-    let animation_handle = spawn_execution_animation(command.to_string(), is_running.clone());
+/// Output of a command that was copied to the terminal while it ran.
+struct Captured {
+    finished: Finished,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
 
-    let output = Command::new(shell)
-        .arg("-c")
-        .arg(command)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .context("Failed to execute command")?;
+/// Run `command`, copying stdout and stderr to the terminal as they arrive and keeping both.
+///
+/// `on_output` runs once, before anything is written; the same lock orders writes from the two
+/// streams, so a chunk is never split by output from the other one. `find` output is enriched
+/// line by line.
+///
+/// The command runs in its own process group under a [`Supervisor`], which passes on Ctrl-C and
+/// stops the whole group once `timeout` passes.
+fn run_streaming(
+    command: &str,
+    shell: &str,
+    timeout: Option<Duration>,
+    on_output: impl FnOnce() + Send,
+) -> Result<Captured> {
+    let mut child = {
+        let mut child = Command::new(shell);
+        child
+            .arg("-c")
+            .arg(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        supervisor::own_group(&mut child);
+        child.spawn().context("Failed to execute command")?
+    };
+    let mut supervisor = Supervisor::new(&child, timeout);
+    let stdout = child.stdout.take().context("Failed to capture stdout")?;
+    let stderr = child.stderr.take().context("Failed to capture stderr")?;
+
+    let enrich = is_find(command);
+    let first_output = std::sync::Mutex::new(Some(on_output));
+    let write = |chunk: &[u8], to_stderr: bool| {
+        let mut pending = first_output.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(on_output) = pending.take() {
+            on_output();
+        }
+        let _ = if to_stderr {
+            let mut stderr = io::stderr();
+            stderr.write_all(chunk).and_then(|()| stderr.flush())
+        } else {
+            let mut stdout = io::stdout();
+            stdout.write_all(chunk).and_then(|()| stdout.flush())
+        };
+    };
 
-    is_running.store(false, Ordering::SeqCst);
-    if let Some(handle) = animation_handle {
-        let _ = handle.join();
+    let (finished, stdout, stderr) = thread::scope(|scope| {
+        let stdout = scope.spawn(|| {
+            let mut partial = Vec::new();
+            let captured = pump(stdout, |chunk| {
+                if !enrich {
+                    return write(chunk, false);
+                }
+                // Only whole lines name a path that can be looked up.
+                partial.extend_from_slice(chunk);
+                if let Some(end) = partial.iter().rposition(|byte| *byte == b'\n') {
+                    let lines: Vec<u8> = partial.drain(..=end).collect();
+                    write(enrich_find_output(command, &lines).as_bytes(), false);
+                }
+            });
+            if !partial.is_empty() {
+                write(enrich_find_output(command, &partial).as_bytes(), false);
+            }
+            captured
+        });
+        let stderr = scope.spawn(|| pump(stderr, |chunk| write(chunk, true)));
+        // Background processes can keep the pipes open, so wait for the command itself first.
+        let finished = supervisor.wait(&mut child);
+        (finished, stdout.join(), stderr.join())
+    });
+    drop(supervisor);
+
+    let finished = finished?;
+    if let Some(on_output) = first_output
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner())
+    {
+        on_output();
     }
 
-    println!();
+    let join = |result: thread::Result<io::Result<Vec<u8>>>| {
+        result
+            .map_err(|_| anyhow!("Output reader panicked"))?
+            .context("Failed to read command output")
+    };
+    Ok(Captured {
+        finished,
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    })
+}
 
-    if !output.stdout.is_empty() {
-        let resolved = enrich_find_output(command, &output.stdout)?;
-        io::stdout().write_all(resolved.as_bytes())?;
-        if !resolved.ends_with('\n') {
-            println!();
+/// Copy `reader` to `sink` chunk by chunk until it closes, returning everything read.
+fn pump(mut reader: impl Read, mut sink: impl FnMut(&[u8])) -> io::Result<Vec<u8>> {
+    let mut captured = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(captured),
+            Ok(read) => {
+                sink(&buffer[..read]);
+                captured.extend_from_slice(&buffer[..read]);
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
         }
     }
+}
 
-    if !output.stderr.is_empty() {
-        io::stderr().write_all(&output.stderr)?;
-        if !output.stderr.ends_with(b"\n") {
-            eprintln!();
+/// End the command's output with a newline so the status line starts on its own line.
+fn finish_line(stdout: &[u8], stderr: &[u8]) {
+    if !stdout.is_empty() && !stdout.ends_with(b"\n") {
+        println!();
+    }
+    if !stderr.is_empty() && !stderr.ends_with(b"\n") {
+        eprintln!();
+    }
+}
+
+/// Back up the files `command` touches so `task undo` can restore them.
+fn snapshot_before_run(command: &str, report: &SafetyReport) {
+    if report.paths.is_empty() {
+        return;
+    }
+    let Some(data_dir) = config::data_dir() else {
+        return;
+    };
+
+    match UndoStore::new(&data_dir).snapshot(command, &report.paths) {
+        Ok(Some(snapshot)) => println!(
+            "{}",
+            format!(
+                "Saved snapshot {}; run `task undo` to restore the affected files.",
+                snapshot.id
+            )
+            .bright_black()
+        ),
+        Ok(None) => {}
+        Err(err) => println!(
+            "{}",
+            format!("No snapshot taken, so this cannot be undone: {err:#}").yellow()
+        ),
+    }
+}
+
+fn handle_undo(id: Option<&str>, list: bool) -> Result<()> {
+    let data_dir =
+        config::data_dir().ok_or_else(|| anyhow!("Could not determine the data directory"))?;
+    let store = UndoStore::new(&data_dir);
+
+    if list {
+        let snapshots = store.list()?;
+        if snapshots.is_empty() {
+            println!("{}", "No snapshots stored.".yellow());
+        }
+        for snapshot in snapshots.iter().rev() {
+            let taken = DateTime::from_timestamp(snapshot.created, 0)
+                .map(|time| {
+                    time.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            println!("{}  {}  {}", snapshot.id.bold(), taken, snapshot.command);
         }
+        return Ok(());
     }
 
-    if output.status.success() {
-        println!("{}", "Command completed successfully.".green());
+    let restored = store.restore(id)?;
+    let snapshot = &restored.snapshot;
+    println!(
+        "{}",
+        format!("Restored snapshot {} ({})", snapshot.id, snapshot.command).green()
+    );
+    for path in snapshot
+        .paths()
+        .filter(|path| !restored.kept.iter().any(|kept| kept == path))
+    {
+        println!("  {}", path.display());
+    }
+    if !restored.kept.is_empty() {
+        println!(
+            "{}",
+            "Left in place, as the command did not create them:".yellow()
+        );
+        for path in &restored.kept {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn print_dry_run_changes(
+    changes: &[sandbox::Change],
+    finished: Finished,
+    timeout: Option<Duration>,
+) {
+    let status = finished.status;
+    if finished.timed_out {
+        let limit = timeout
+            .map(|timeout| format!(" after {timeout:?}"))
+            .unwrap_or_default();
+        println!(
+            "{}",
+            format!("Dry run timed out{limit} and was stopped ({status}).").yellow()
+        );
     } else {
         println!(
             "{}",
-            format!("Command exited with status: {}", output.status).red()
+            format!("Dry run finished with {status}.").bright_blue()
         );
     }
+    if changes.is_empty() {
+        println!(
+            "{}",
+            "No files in the working directory would change.".green()
+        );
+        return;
+    }
 
-    Ok(())
+    println!("{}", "Files that would change:".bright_blue());
+    for change in changes {
+        let line = format!("  {:<8} {}", change.kind, change.path);
+        println!(
+            "{}",
+            match change.kind {
+                sandbox::ChangeKind::Created => line.green(),
+                sandbox::ChangeKind::Modified => line.yellow(),
+                sandbox::ChangeKind::Deleted => line.red(),
+            }
+        );
+    }
+}
+
+/// The animation shown while a command runs; it is cleared when stopped or dropped, so an early
+/// return never leaves it spinning over the command's output.
+struct Animation {
+    is_running: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Animation {
+    fn start(command: &str) -> Self {
+        let is_running = Arc::new(AtomicBool::new(true));
+        let handle = spawn_execution_animation(command.to_string(), is_running.clone());
+        Animation { is_running, handle }
+    }
+
+    fn stop(&mut self) {
+        self.is_running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Animation {
+    fn drop(&mut self) {
+        self.stop();
+    }
 }
 
 fn spawn_execution_animation(
@@ -494,8 +1283,9 @@ fn spawn_execution_animation(
             }
         }
 
+        // Reset the colours too, in case a frame was cut short.
         let mut stderr = io::stderr();
-        let _ = write!(stderr, "\r\x1b[2K");
+        let _ = write!(stderr, "\r\x1b[2K\x1b[0m");
         let _ = stderr.flush();
     }))
 }
@@ -523,7 +1313,7 @@ fn render_gradient(text: &str, pulse_pos: f32) -> String {
     out
 }
 
-fn generate_completions(shell: Shell) {
+fn generate_completions(shell: Shell) -> Result<()> {
     use clap_complete::{generate, shells};
     use std::io;
 
@@ -531,6 +1321,26 @@ fn generate_completions(shell: Shell) {
     match shell {
         Shell::Bash => generate(shells::Bash, &mut cmd, "task", &mut io::stdout()),
         Shell::Zsh => generate(shells::Zsh, &mut cmd, "task", &mut io::stdout()),
+        Shell::Fish => generate(shells::Fish, &mut cmd, "task", &mut io::stdout()),
+        Shell::Nu => generate(
+            clap_complete_nushell::Nushell,
+            &mut cmd,
+            "task",
+            &mut io::stdout(),
+        ),
+        Shell::Sh => return Err(anyhow!("POSIX sh has no programmable completion")),
+    }
+    Ok(())
+}
+
+/// Key bindings for `task init`; each calls `task gen --emit-only` on the prompt line.
+fn init_script(shell: Shell) -> Result<&'static str> {
+    match shell {
+        Shell::Bash => Ok(include_str!("init/task.bash")),
+        Shell::Zsh => Ok(include_str!("init/task.zsh")),
+        Shell::Fish => Ok(include_str!("init/task.fish")),
+        Shell::Nu => Ok(include_str!("init/task.nu")),
+        Shell::Sh => Err(anyhow!("POSIX sh has no line editor to bind a key in")),
     }
 }
 
@@ -539,14 +1349,21 @@ impl Shell {
         match value.to_lowercase().as_str() {
             "bash" => Some(Shell::Bash),
             "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "sh" | "dash" | "posix" => Some(Shell::Sh),
+            "nu" | "nushell" => Some(Shell::Nu),
             _ => None,
         }
     }
 }
 
-fn enrich_find_output(command: &str, stdout: &[u8]) -> Result<String> {
-    if !command.trim_start().starts_with("find") {
-        return Ok(String::from_utf8_lossy(stdout).into_owned());
+fn is_find(command: &str) -> bool {
+    command.trim_start().starts_with("find")
+}
+
+fn enrich_find_output(command: &str, stdout: &[u8]) -> String {
+    if !is_find(command) {
+        return String::from_utf8_lossy(stdout).into_owned();
     }
 
     let output_str = String::from_utf8_lossy(stdout);
@@ -582,7 +1399,7 @@ fn enrich_find_output(command: &str, stdout: &[u8]) -> Result<String> {
         enriched.push('\n');
     }
 
-    Ok(enriched)
+    enriched
 }
 
 fn format_size(bytes: u64) -> String {
@@ -601,15 +1418,17 @@ fn format_size(bytes: u64) -> String {
     format!("{} {}", bytes, unit)
 }
 
-fn prompt_for_command_selection(commands: &[String]) -> Result<Option<String>> {
+fn prompt_for_command_selection(
+    commands: &[(String, SafetyReport)],
+) -> Result<Option<(String, SafetyReport)>> {
     if commands.is_empty() {
         return Ok(None);
     }
 
     loop {
         println!("{}", "Select a command to run:".cyan());
-        for (idx, command) in commands.iter().enumerate() {
-            println!("  {}) {}", idx + 1, command);
+        for (idx, (command, verdict)) in commands.iter().enumerate() {
+            println!("  {}) {}{}", idx + 1, command, safety_note(verdict));
         }
         println!("  0) Cancel");
         print!("Enter choice (default 0): ");
@@ -627,6 +1446,13 @@ fn prompt_for_command_selection(commands: &[String]) -> Result<Option<String>> {
             .ok()
             .filter(|idx| (1..=commands.len()).contains(idx))
         {
+            if commands[idx - 1].1.tier == Tier::Deny {
+                println!(
+                    "{}",
+                    "That command is blocked by safety rules; choose another.".red()
+                );
+                continue;
+            }
             return Ok(Some(commands[idx - 1].clone()));
         }
         println!("{}", "Invalid selection, please try again.".yellow());
@@ -642,26 +1468,220 @@ fn executable_command(value: &str) -> Option<String> {
     }
 }
 
-fn confirm_and_execute(command: &str, shell: &str) -> Result<()> {
+fn print_redactions(redactions: &[Redaction]) {
+    if redactions.is_empty() {
+        println!(
+            "{}",
+            "Nothing was masked in the description.".bright_black()
+        );
+        return;
+    }
+
     println!(
-        "\n{}",
-        "The following command will be executed:".bright_blue()
+        "{}",
+        "Masked before sending the description:".bright_black()
     );
-    println!("{}", format!("{} -c \"{}\"", shell, command).bold());
+    for redaction in redactions {
+        println!(
+            "  {:<12} {:<8} -> {}",
+            redaction.kind,
+            redaction.preview(),
+            redaction.placeholder
+        );
+    }
+    println!();
+}
 
-    println!("{}", "Proceed with execution? [y/N] ".bright_blue());
-    io::stdout().flush().context("Failed to flush stdout")?;
+/// Menu annotation for commands the safety policy blocks or wants confirmed.
+fn safety_note(report: &SafetyReport) -> String {
+    let Some(finding) = report.decisive().next() else {
+        return String::new();
+    };
+    match report.tier {
+        Tier::Allow => String::new(),
+        Tier::Confirm => format!("  [needs confirmation: {}]", finding.reason)
+            .bright_yellow()
+            .to_string(),
+        Tier::Deny => format!("  [blocked: {}]", finding.reason).red().to_string(),
+    }
+}
 
-    let mut answer = String::new();
-    io::stdin()
-        .read_line(&mut answer)
-        .context("Failed to read confirmation input")?;
+/// Print what a command can do and why, colored by how risky it is.
+fn print_safety_report(report: &SafetyReport) {
+    let risk = format!("Risk: {}.", report.level);
+    let risk = match report.level {
+        RiskLevel::ReadOnly => risk.green(),
+        RiskLevel::NetworkEgress | RiskLevel::ModifiesFiles => risk.yellow(),
+        RiskLevel::Destructive | RiskLevel::Privileged => risk.bright_red(),
+    };
+    println!("{} {}", risk, report.explanation());
+}
 
-    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
-        maybe_execute(command, shell, true)?;
-    } else {
-        println!("{}", "Command not executed.".yellow());
+/// Destructive or privileged commands, and those the policy flags, need their name typed out.
+fn needs_typed_confirmation(report: &SafetyReport) -> bool {
+    report.tier == Tier::Confirm || report.level >= RiskLevel::Destructive
+}
+
+fn confirm_and_execute(
+    policy: &Policy,
+    command: &str,
+    report: &SafetyReport,
+    shell: &str,
+    dry_run: bool,
+    tty: bool,
+    timeout: Option<Duration>,
+) -> Result<ExitCode> {
+    let mut command = command.to_string();
+    let mut report = report.clone();
+
+    loop {
+        println!();
+        print_safety_report(&report);
+        if report.tier == Tier::Deny {
+            println!(
+                "{}",
+                "Refusing to run a command blocked by safety rules.".red()
+            );
+            return Ok(Exit::Blocked.into());
+        }
+
+        if dry_run {
+            if let Some(reason) = sandbox::unsupported(&command) {
+                println!("{}", format!("Skipping the dry run: {reason}.").yellow());
+            } else {
+                println!(
+                    "{}",
+                    "Dry run: no network, and only the working directory is writable."
+                        .bright_blue()
+                );
+                if let Err(err) =
+                    maybe_execute(&command, &report, shell, Execution::DryRun, timeout)
+                {
+                    println!("{}", format!("Dry run failed: {err:#}").yellow());
+                }
+            }
+        }
+
+        println!(
+            "\n{}",
+            "The following command will be executed:".bright_blue()
+        );
+        println!("{}", format!("{} -c \"{}\"", shell, command).bold());
+
+        let expected = needs_typed_confirmation(&report).then(|| {
+            report
+                .decisive()
+                .find_map(|finding| finding.argv.first())
+                .map(String::as_str)
+                .or_else(|| command.split_whitespace().next())
+                .unwrap_or_default()
+                .to_string()
+        });
+
+        match &expected {
+            Some(name) => println!(
+                "{}",
+                format!("Type '{name}' to proceed with execution, or 'e' to edit: ").bright_blue()
+            ),
+            None => println!("{}", "Proceed with execution? [y/N/e] ".bright_blue()),
+        }
+        io::stdout().flush().context("Failed to flush stdout")?;
+
+        let mut answer = String::new();
+        let read = io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to read confirmation input")?;
+        if read == 0 {
+            println!("{}", "No answer on stdin; command not executed.".yellow());
+            return Ok(Exit::NotInteractive.into());
+        }
+
+        if matches!(answer.trim(), "e" | "E") && expected.as_deref() != Some(answer.trim()) {
+            match edit::edit_command(&command) {
+                Ok(Some(edited)) if !edited.trim().is_empty() => {
+                    // The edit may have made the command riskier, so it is checked from scratch;
+                    // a blocked edit is refused at the top of the loop.
+                    command = edited.trim().to_string();
+                    report = match enforce_safety(policy, &command) {
+                        Ok(report) => report,
+                        Err(err) => match err.downcast::<Blocked>() {
+                            Ok(blocked) => blocked.report,
+                            Err(err) => return Err(err),
+                        },
+                    };
+                }
+                Ok(_) => println!("{}", "Keeping the command unchanged.".yellow()),
+                Err(err) => println!(
+                    "{}",
+                    format!("{err:#}; keeping the command unchanged.").yellow()
+                ),
+            }
+            continue;
+        }
+
+        let confirmed = match &expected {
+            Some(name) => answer.trim() == name,
+            None => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        };
+        if !confirmed {
+            println!("{}", "Command not executed.".yellow());
+            return Ok(Exit::Declined.into());
+        }
+        // Answers may be piped in, but the command itself needs someone at the terminal.
+        if !atty::is(atty::Stream::Stdin) {
+            println!(
+                "{}",
+                "Non-interactive session detected; skipping execution.".yellow()
+            );
+            return Ok(Exit::NotInteractive.into());
+        }
+
+        let execution = if tty || pty::needs_terminal(&command) {
+            Execution::Terminal
+        } else {
+            Execution::Real
+        };
+        return Ok(
+            match maybe_execute(&command, &report, shell, execution, timeout)? {
+                Some(finished) if finished.timed_out => Exit::TimedOut.into(),
+                Some(finished) => status_code(finished.status),
+                None => Exit::Declined.into(),
+            },
+        );
     }
+}
 
-    Ok(())
+fn print_policy_check(policy: &Policy, command: &str) {
+    let report = policy.evaluate(command);
+    let label = format!("Verdict: {}", report.tier);
+    println!(
+        "{}",
+        match report.tier {
+            Tier::Allow => label.green(),
+            Tier::Confirm => label.bright_yellow(),
+            Tier::Deny => label.red(),
+        }
+    );
+    print_safety_report(&report);
+
+    for finding in &report.findings {
+        println!(
+            "  {} {}: {} ({})",
+            finding.tier,
+            finding.argv.join(" ").bold(),
+            finding.reason,
+            finding.rule
+        );
+    }
+
+    let mut sources = policy.sources().peekable();
+    if sources.peek().is_none() {
+        println!(
+            "{}",
+            "No policy files loaded; built-in rules only.".yellow()
+        );
+    }
+    for (scope, source) in sources {
+        println!("{} {} ({})", "Policy:".green(), source, scope);
+    }
 }
@@ -0,0 +1,593 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::safety::{self, RiskLevel, WorkingDir};
+
+mod scope;
+
+/// System-wide policy, typically managed by an administrator.
+const SYSTEM_POLICY: &str = "/etc/task.sh/policy.toml";
+/// Per-project policy, looked up from the working directory upwards.
+const PROJECT_POLICY: &str = ".task-policy.toml";
+
+/// How a command is treated, from most to least permissive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    #[default]
+    Allow,
+    /// The user must type the command name before it runs.
+    Confirm,
+    Deny,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tier::Allow => "allow",
+            Tier::Confirm => "confirm",
+            Tier::Deny => "deny",
+        })
+    }
+}
+
+/// A policy rule matched against each simple command's name, flags and paths.
+///
+/// Every field that is set must match.
+#[derive(Debug, Deserialize, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    /// Command name, e.g. `kubectl`; `*` matches any command.
+    pub command: String,
+    /// Words that must appear among the non-option arguments, in this order.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Options that must all be present; `-r` also matches grouped options such as `-rf`.
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Glob patterns (`*`, `**`, `?`) of which at least one argument must match one.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Shown when the rule matches.
+    pub reason: Option<String>,
+}
+
+/// Rules of one policy file, or of the `[safety]` table in the config file.
+#[derive(Debug, Deserialize, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyFile {
+    #[serde(default)]
+    pub allow: Vec<PolicyRule>,
+    #[serde(default)]
+    pub confirm: Vec<PolicyRule>,
+    #[serde(default)]
+    pub deny: Vec<PolicyRule>,
+    /// How to treat commands that write outside the project tree; unset leaves them alone.
+    pub outside_project: Option<Tier>,
+}
+
+/// Where a policy layer was loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    System,
+    User,
+    Project,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::System => "system",
+            Scope::User => "user",
+            Scope::Project => "project",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    scope: Scope,
+    source: String,
+    rules: PolicyFile,
+}
+
+/// A rule that matched while evaluating a command, and what it decided.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub tier: Tier,
+    /// The matching rule, e.g. `built-in rule recursive-force-rm`.
+    pub rule: String,
+    pub reason: String,
+    pub argv: Vec<String>,
+}
+
+/// Outcome of checking a command against the built-in rules and the loaded policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SafetyReport {
+    /// Whether the command may run, needs confirmation, or is refused.
+    pub tier: Tier,
+    /// What the command can do, regardless of what the policy decided.
+    pub level: RiskLevel,
+    /// Every rule that matched, built-in or from a policy file.
+    pub findings: Vec<Finding>,
+    /// Files the command creates, changes or deletes.
+    pub paths: Vec<String>,
+}
+
+impl SafetyReport {
+    /// Findings that decided the report's tier.
+    pub fn decisive(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.tier == self.tier)
+    }
+
+    /// A short human explanation: what the command does, what it touches and which rules matched.
+    pub fn explanation(&self) -> String {
+        let mut text = self.level.describe().to_string();
+        if !self.paths.is_empty() {
+            text.push_str(&format!(" Affects {}.", self.paths.join(", ")));
+        }
+        for finding in self.decisive() {
+            text.push_str(&format!(
+                " {} ({}).",
+                capitalize(&finding.reason),
+                finding.rule
+            ));
+        }
+        text
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Safety policy layered from system, user and project files on top of the built-in rules.
+///
+/// Deny rules win over confirm rules, which win over allow rules, whichever layer they come from.
+/// Built-in rules deny by default; matching allow or confirm rules from the system or user layer
+/// waive or soften them. Project files come with checked-out code, so they can only add
+/// restrictions.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    layers: Vec<Layer>,
+}
+
+impl Policy {
+    /// Load the system policy, the user policy and `[safety]` table, and the nearest project policy.
+    pub fn load(config_rules: &PolicyFile) -> Result<Self> {
+        let mut policy = Policy::default();
+
+        policy.load_file(Scope::System, Path::new(SYSTEM_POLICY))?;
+        if let Some(dir) = dirs::config_dir() {
+            policy.load_file(Scope::User, &dir.join("task.sh").join("policy.toml"))?;
+        }
+        policy.push(Scope::User, "[safety] in config file", config_rules.clone());
+        if let Some(path) = env::current_dir()
+            .ok()
+            .and_then(|dir| find_project_policy(&dir))
+        {
+            policy.load_file(Scope::Project, &path)?;
+        }
+
+        Ok(policy)
+    }
+
+    fn load_file(&mut self, scope: Scope, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file at {}", path.display()))?;
+        let rules: PolicyFile = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse policy file at {}", path.display()))?;
+        self.push(scope, &path.display().to_string(), rules);
+        Ok(())
+    }
+
+    fn push(&mut self, scope: Scope, source: &str, rules: PolicyFile) {
+        if rules != PolicyFile::default() {
+            self.layers.push(Layer {
+                scope,
+                source: source.to_string(),
+                rules,
+            });
+        }
+    }
+
+    /// Sources of the loaded layers, in evaluation order.
+    pub fn sources(&self) -> impl Iterator<Item = (Scope, &str)> {
+        self.layers
+            .iter()
+            .map(|layer| (layer.scope, layer.source.as_str()))
+    }
+
+    /// Check `command` against the built-in rules and every policy layer.
+    pub fn evaluate(&self, command: &str) -> SafetyReport {
+        let analysis = safety::analyze(command);
+        let mut findings = Vec::new();
+
+        for violation in analysis.violations {
+            let builtin = format!("built-in rule {}", violation.rule);
+            // Commands that could not be parsed cannot be matched against any rule.
+            let waiver = (!violation.argv.is_empty())
+                .then(|| self.waiver(&violation.argv))
+                .flatten();
+            findings.push(match waiver {
+                Some((tier, rule)) => Finding {
+                    tier,
+                    rule: format!("{builtin}, overridden by {rule}"),
+                    reason: violation.reason,
+                    argv: violation.argv,
+                },
+                None => Finding {
+                    tier: Tier::Deny,
+                    rule: builtin,
+                    reason: violation.reason,
+                    argv: violation.argv,
+                },
+            });
+        }
+
+        for argv in &analysis.commands {
+            for layer in &self.layers {
+                for (tier, rules) in [
+                    (Tier::Deny, &layer.rules.deny),
+                    (Tier::Confirm, &layer.rules.confirm),
+                ] {
+                    for (index, rule) in rules.iter().enumerate() {
+                        if rule.matches(argv) {
+                            findings.push(Finding {
+                                tier,
+                                rule: describe(layer, tier, index),
+                                reason: rule
+                                    .reason
+                                    .clone()
+                                    .unwrap_or_else(|| format!("matches {tier} rule")),
+                                argv: argv.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((tier, rule)) = self.scope_guard()
+            && !analysis.paths.is_empty()
+            && let Ok(cwd) = env::current_dir()
+        {
+            let root = scope::project_root(&cwd);
+            for path in &analysis.paths {
+                if safety::HARMLESS_DEVICES.contains(&path.as_str()) {
+                    continue;
+                }
+                let working_dir = analysis
+                    .working_dirs
+                    .iter()
+                    .find(|(written, _)| written == path)
+                    .map(|(_, dir)| dir);
+                let outside = match working_dir {
+                    None => scope::escapes(&root, &cwd, path),
+                    Some(WorkingDir::Literal(dir)) => match scope::change_dir(&cwd, dir) {
+                        Some(dir) => scope::escapes(&root, &dir, path),
+                        None => Some(format!("{dir}/{path}")),
+                    },
+                    Some(WorkingDir::Dynamic) => {
+                        Some(format!("{path} in a directory only known at runtime"))
+                    }
+                };
+                if let Some(outside) = outside {
+                    findings.push(Finding {
+                        tier,
+                        rule: rule.clone(),
+                        reason: format!("writes outside the project to {outside}"),
+                        argv: analysis
+                            .commands
+                            .iter()
+                            .find(|argv| argv.iter().skip(1).any(|arg| arg.contains(path.as_str())))
+                            .cloned()
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        SafetyReport {
+            tier: findings
+                .iter()
+                .map(|finding| finding.tier)
+                .max()
+                .unwrap_or_default(),
+            level: analysis.level,
+            findings,
+            paths: analysis.paths,
+        }
+    }
+
+    /// The strictest `outside_project` setting of any layer, and where it comes from.
+    fn scope_guard(&self) -> Option<(Tier, String)> {
+        self.layers
+            .iter()
+            .filter_map(|layer| layer.rules.outside_project.map(|tier| (tier, layer)))
+            .filter(|(tier, _)| *tier > Tier::Allow)
+            .max_by_key(|(tier, _)| *tier)
+            .map(|(tier, layer)| {
+                (
+                    tier,
+                    format!(
+                        "outside_project in {} policy ({})",
+                        layer.scope, layer.source
+                    ),
+                )
+            })
+    }
+
+    /// The most permissive-but-safe override for a built-in violation: confirm beats allow.
+    fn waiver(&self, argv: &[String]) -> Option<(Tier, String)> {
+        let trusted = self
+            .layers
+            .iter()
+            .filter(|layer| layer.scope != Scope::Project);
+
+        let mut waiver = None;
+        for layer in trusted {
+            for (tier, rules) in [
+                (Tier::Confirm, &layer.rules.confirm),
+                (Tier::Allow, &layer.rules.allow),
+            ] {
+                if let Some(index) = rules.iter().position(|rule| rule.matches(argv)) {
+                    let candidate = (tier, describe(layer, tier, index));
+                    if waiver.as_ref().is_none_or(|(current, _)| tier > *current) {
+                        waiver = Some(candidate);
+                    }
+                }
+            }
+        }
+        waiver
+    }
+}
+
+fn describe(layer: &Layer, tier: Tier, index: usize) -> String {
+    format!(
+        "{tier} rule #{} in {} policy ({})",
+        index + 1,
+        layer.scope,
+        layer.source
+    )
+}
+
+impl PolicyRule {
+    fn matches(&self, argv: &[String]) -> bool {
+        let Some((name, args)) = argv.split_first() else {
+            return false;
+        };
+        let name = Path::new(name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(name);
+        if self.command != "*" && self.command != name {
+            return false;
+        }
+
+        let options: Vec<&str> = args
+            .iter()
+            .map(String::as_str)
+            .take_while(|arg| *arg != "--")
+            .filter(|arg| arg.starts_with('-') && *arg != "-")
+            .collect();
+        let flags_match = self.flags.iter().all(|flag| {
+            options.iter().any(|option| match flag.strip_prefix("--") {
+                Some(_) => option == flag || option.starts_with(&format!("{flag}=")),
+                None => {
+                    let letters = flag.trim_start_matches('-');
+                    !option.starts_with("--")
+                        && letters
+                            .chars()
+                            .all(|letter| option.trim_start_matches('-').contains(letter))
+                }
+            })
+        });
+
+        let operands: Vec<&str> = args
+            .iter()
+            .map(String::as_str)
+            .filter(|arg| !options.contains(arg))
+            .collect();
+        let mut remaining = operands.iter();
+        let args_match = self
+            .args
+            .iter()
+            .all(|expected| remaining.any(|operand| operand == expected));
+
+        let paths_match = self.paths.is_empty()
+            || operands.iter().any(|operand| {
+                self.paths
+                    .iter()
+                    .any(|pattern| glob_match(pattern, operand))
+            });
+
+        flags_match && args_match && paths_match
+    }
+}
+
+/// Match `text` against a glob where `*` stays within a path segment and `**` crosses them.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=text.len()).any(|skip| matches(rest, &text[skip..])),
+            [b'*', rest @ ..] => (0..=text.len())
+                .take_while(|skip| *skip == 0 || text[skip - 1] != b'/')
+                .any(|skip| matches(rest, &text[skip..])),
+            [b'?', rest @ ..] => !text.is_empty() && text[0] != b'/' && matches(rest, &text[1..]),
+            [expected, rest @ ..] => text.first() == Some(expected) && matches(rest, &text[1..]),
+        }
+    }
+
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+fn find_project_policy(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_POLICY))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(layers: &[(Scope, &str)]) -> Policy {
+        let mut policy = Policy::default();
+        for (scope, contents) in layers {
+            policy.push(*scope, "test", toml::from_str(contents).unwrap());
+        }
+        policy
+    }
+
+    #[test]
+    fn user_allow_waives_builtin_rule() {
+        let policy = policy(&[(
+            Scope::User,
+            "[[allow]]\ncommand = \"sudo\"\nargs = [\"apt\", \"install\"]",
+        )]);
+
+        let verdict = policy.evaluate("sudo apt install ripgrep");
+        assert_eq!(verdict.tier, Tier::Allow);
+        assert!(
+            verdict.findings[0]
+                .rule
+                .contains("overridden by allow rule #1")
+        );
+
+        assert_eq!(policy.evaluate("sudo apt remove ripgrep").tier, Tier::Deny);
+    }
+
+    #[test]
+    fn deny_rules_match_args_flags_and_paths() {
+        let policy = policy(&[(
+            Scope::System,
+            "[[deny]]\ncommand = \"kubectl\"\nargs = [\"delete\", \"ns\"]\nreason = \"namespaces are shared\"\n\n[[deny]]\ncommand = \"git\"\nargs = [\"push\"]\nflags = [\"--force\"]\n\n[[confirm]]\ncommand = \"cp\"\nflags = [\"-r\"]\npaths = [\"/srv/**\"]",
+        )]);
+
+        let verdict = policy.evaluate("kubectl -n x delete ns payments");
+        assert_eq!(verdict.tier, Tier::Deny);
+        assert_eq!(verdict.findings[0].reason, "namespaces are shared");
+
+        assert_eq!(policy.evaluate("kubectl get ns").tier, Tier::Allow);
+        assert_eq!(
+            policy.evaluate("git push --force origin main").tier,
+            Tier::Deny
+        );
+        assert_eq!(policy.evaluate("git push origin main").tier, Tier::Allow);
+        assert_eq!(
+            policy.evaluate("cp -rv ./site /srv/www/site").tier,
+            Tier::Confirm
+        );
+        assert_eq!(policy.evaluate("cp -rv ./site /tmp/site").tier, Tier::Allow);
+    }
+
+    #[test]
+    fn confirm_softens_builtin_rule_and_deny_wins() {
+        let policy = policy(&[
+            (
+                Scope::User,
+                "[[confirm]]\ncommand = \"rm\"\nflags = [\"-r\", \"-f\"]",
+            ),
+            (Scope::System, "[[deny]]\ncommand = \"rm\"\npaths = [\"/\"]"),
+        ]);
+
+        assert_eq!(policy.evaluate("rm -rf ./build").tier, Tier::Confirm);
+        assert_eq!(policy.evaluate("rm -rf /").tier, Tier::Deny);
+    }
+
+    #[test]
+    fn project_layer_cannot_waive_builtin_rules() {
+        let policy = policy(&[(
+            Scope::Project,
+            "[[allow]]\ncommand = \"rm\"\n\n[[confirm]]\ncommand = \"make\"\nargs = [\"deploy\"]",
+        )]);
+
+        assert_eq!(policy.evaluate("rm -rf ./build").tier, Tier::Deny);
+        assert_eq!(policy.evaluate("make deploy").tier, Tier::Confirm);
+    }
+
+    #[test]
+    fn report_explains_level_paths_and_rules() {
+        let report = Policy::default().evaluate("rm -rf ./build");
+
+        assert_eq!(report.tier, Tier::Deny);
+        assert_eq!(report.level, RiskLevel::Destructive);
+        assert_eq!(report.paths, ["./build"]);
+        assert_eq!(
+            report.explanation(),
+            "Deletes or overwrites data, which cannot be undone. Affects ./build. `rm` deletes \
+             recursively without prompting (built-in rule recursive-force-rm)."
+        );
+    }
+
+    #[test]
+    fn outside_project_guard_uses_strictest_layer() {
+        let cwd = env::current_dir().unwrap();
+        let outside = cwd.parent().unwrap().join("notes.txt");
+        let command = format!("cp notes.txt {}", outside.display());
+
+        let off = Policy::default().evaluate(&command);
+        assert_eq!(off.tier, Tier::Allow);
+
+        let policy = policy(&[
+            (Scope::User, "outside_project = \"confirm\""),
+            (Scope::Project, "outside_project = \"deny\""),
+        ]);
+        let report = policy.evaluate(&command);
+        assert_eq!(report.tier, Tier::Deny);
+        let finding = report.decisive().next().unwrap();
+        assert_eq!(finding.rule, "outside_project in project policy (test)");
+        assert_eq!(finding.argv[0], "cp");
+        assert_eq!(policy.evaluate("cp notes.txt backup.txt").tier, Tier::Allow);
+        assert_eq!(policy.evaluate("ls ~ > /dev/null").tier, Tier::Allow);
+    }
+
+    #[test]
+    fn outside_project_guard_follows_cd() {
+        let policy = policy(&[(Scope::Project, "outside_project = \"deny\"")]);
+
+        for inside in [
+            "cd src && touch notes.txt",
+            "cd src; cd .. && rm -f notes.txt",
+            "echo $(cd / && pwd) > root.txt",
+            "cd / | true; touch notes.txt",
+        ] {
+            assert_eq!(policy.evaluate(inside).tier, Tier::Allow, "{inside}");
+        }
+        for outside in [
+            "cd .. && touch notes.txt",
+            "cd src; cd ../.. ; echo hi > notes.txt",
+            "pushd /etc && rm -f hosts",
+            "cd \"$(mktemp -d)\" && touch notes.txt",
+            "cd && touch notes.txt",
+        ] {
+            assert_eq!(policy.evaluate(outside).tier, Tier::Deny, "{outside}");
+        }
+    }
+
+    #[test]
+    fn glob_segments() {
+        assert!(glob_match("/srv/*", "/srv/www"));
+        assert!(!glob_match("/srv/*", "/srv/www/site"));
+        assert!(glob_match("/srv/**", "/srv/www/site"));
+        assert!(glob_match("*.sql", "dump.sql"));
+    }
+}
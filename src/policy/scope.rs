@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use super::glob_match;
+
+/// The tree writes are confined to: the enclosing git work tree, or `cwd` outside of one.
+pub(super) fn project_root(cwd: &Path) -> PathBuf {
+    let root = cwd
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(cwd);
+    resolve_existing(root)
+}
+
+/// Where a path written by a command lands, if that is outside `root`.
+///
+/// `~` and variables are expanded, `..` is applied and symlinks are followed as far as the path
+/// exists. A wildcard in the last component is checked against every match. Paths that cannot be
+/// expanded count as outside, since nothing says where they point.
+pub(super) fn escapes(root: &Path, cwd: &Path, written: &str) -> Option<String> {
+    let Ok(expanded) = shellexpand::full(written) else {
+        return Some(written.to_string());
+    };
+    let path = lexical(&cwd.join(expanded.as_ref()));
+
+    // Only the components before the first wildcard name a fixed location.
+    let fixed: PathBuf = path
+        .components()
+        .take_while(|component| !is_glob(component))
+        .collect();
+    let mut candidates = vec![resolve_existing(&fixed)];
+    if path.parent() == Some(fixed.as_path())
+        && let Some(pattern) = path.file_name().map(|name| name.to_string_lossy())
+        && pattern.contains(['*', '?', '['])
+        && let Ok(entries) = fs::read_dir(&fixed)
+    {
+        candidates.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| glob_match(&pattern, &entry.file_name().to_string_lossy()))
+                .map(|entry| resolve_existing(&entry.path())),
+        );
+    }
+
+    candidates
+        .into_iter()
+        .find(|candidate| !candidate.starts_with(root))
+        .map(|candidate| candidate.display().to_string())
+}
+
+/// The directory `cd target` leads to from `cwd`, unless `target` cannot be expanded.
+pub(super) fn change_dir(cwd: &Path, target: &str) -> Option<PathBuf> {
+    let expanded = shellexpand::full(target).ok()?;
+    Some(lexical(&cwd.join(expanded.as_ref())))
+}
+
+fn is_glob(component: &Component) -> bool {
+    matches!(component, Component::Normal(name) if name.to_string_lossy().contains(['*', '?', '[']))
+}
+
+/// Apply `.` and `..` without touching the filesystem.
+fn lexical(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Canonicalize the longest existing ancestor of `path` and append the rest unchanged.
+fn resolve_existing(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(real) = ancestor.canonicalize() {
+            return real.join(path.strip_prefix(ancestor).unwrap_or(Path::new("")));
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_writes_that_leave_the_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("project");
+        let cwd = root.join("src");
+        fs::create_dir_all(&cwd).unwrap();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(cwd.join("main.rs"), "").unwrap();
+        assert_eq!(project_root(&cwd), root);
+
+        for inside in ["main.rs", "./out/new.txt", "../README.md", "*.rs", "."] {
+            assert_eq!(escapes(&root, &cwd, inside), None, "{inside}");
+        }
+        for outside in [
+            "../../notes.txt",
+            "/etc/hosts",
+            "sub/../../../x",
+            "../../*",
+            "${TASK_SH_SCOPE_TEST_UNSET}/x",
+        ] {
+            assert!(escapes(&root, &cwd, outside).is_some(), "{outside}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_out_of_the_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("project");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir(dir.path().join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("elsewhere"), root.join("link.d")).unwrap();
+
+        assert!(escapes(&root, &root, "link.d/file").is_some());
+        assert!(escapes(&root, &root, "*.d").is_some());
+    }
+}
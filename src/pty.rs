@@ -0,0 +1,330 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Result;
+
+use crate::{safety, supervisor::Finished};
+
+/// Programs that take over the terminal or keep reading from it while they run.
+const INTERACTIVE: &[&str] = &[
+    "btop", "emacs", "fzf", "htop", "less", "man", "mc", "more", "mutt", "nano", "ncdu", "nvim",
+    "ranger", "screen", "tig", "tmux", "top", "vi", "vim", "watch",
+];
+
+/// Programs that start an interactive session when run without arguments.
+const REPLS: &[&str] = &[
+    "bash", "ghci", "irb", "node", "python", "python3", "sh", "zsh",
+];
+
+/// Whether any simple command in `command` needs a terminal to be usable.
+pub fn needs_terminal(command: &str) -> bool {
+    safety::analyze(command)
+        .commands
+        .iter()
+        .any(|argv| is_interactive(argv))
+}
+
+fn is_interactive(argv: &[String]) -> bool {
+    let Some((name, args)) = argv.split_first() else {
+        return false;
+    };
+    let name = Path::new(name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(name);
+
+    match name {
+        "git" => git_is_interactive(args),
+        // `psql db` or `ssh host` still open a session; only a command or script makes them batch.
+        "psql" | "mysql" | "sqlite3" | "redis-cli" => {
+            !args.iter().any(|arg| matches!(arg.as_str(), "-c" | "-e"))
+        }
+        "ssh" => args.iter().filter(|arg| !arg.starts_with('-')).count() <= 1,
+        _ if REPLS.contains(&name) => args.is_empty(),
+        _ => INTERACTIVE.contains(&name),
+    }
+}
+
+/// `git` subcommands that open an editor or ask about each change.
+fn git_is_interactive(args: &[String]) -> bool {
+    let has = |flags: &[&str]| args.iter().any(|arg| flags.contains(&arg.as_str()));
+    let subcommand = args.iter().find(|arg| !arg.starts_with('-'));
+    match subcommand.map(String::as_str) {
+        Some("rebase") => has(&["-i", "--interactive"]),
+        Some("add" | "checkout" | "reset" | "restore" | "stash") => {
+            has(&["-i", "--interactive", "-p", "--patch"])
+        }
+        Some("commit") => !has(&[
+            "-m",
+            "--message",
+            "-F",
+            "--file",
+            "-C",
+            "--reuse-message",
+            "--no-edit",
+        ]),
+        Some("mergetool" | "difftool") => true,
+        _ => false,
+    }
+}
+
+/// Run `command` with `shell` on a pseudo-terminal connected to this one.
+///
+/// Keystrokes are forwarded verbatim while this terminal is in raw mode, window size changes are
+/// passed on, and the terminal settings are restored afterwards, also when the command fails.
+/// The command's session is stopped once `timeout` passes.
+#[cfg(unix)]
+pub fn run(command: &str, shell: &str, timeout: Option<Duration>) -> Result<Finished> {
+    unix::run(command, shell, timeout)
+}
+
+#[cfg(not(unix))]
+pub fn run(_command: &str, _shell: &str, _timeout: Option<Duration>) -> Result<Finished> {
+    anyhow::bail!("Running commands on a terminal needs a Unix pseudo-terminal")
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs::File,
+        io::{self, Read, Write},
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::process::CommandExt,
+        },
+        process::{Child, Command, Stdio},
+        ptr,
+        time::Duration,
+    };
+
+    use anyhow::{Context, Result};
+
+    use crate::supervisor::{Finished, Supervisor};
+
+    /// How long to wait for input before checking the window size and the child again.
+    const POLL_INTERVAL_MS: libc::c_int = 100;
+
+    pub fn run(command: &str, shell: &str, timeout: Option<Duration>) -> Result<Finished> {
+        let mut size = window_size();
+        let raw_mode = RawMode::enter();
+        let (master, slave) = open_pty(raw_mode.as_ref().map(|mode| mode.original), size)
+            .context("Failed to open a pseudo-terminal")?;
+
+        // The parent's copies of the terminal side close with `Command`, so reads end with the command.
+        let mut child = {
+            let mut child = Command::new(shell);
+            child
+                .arg("-c")
+                .arg(command)
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?))
+                .stderr(Stdio::from(slave));
+            // SAFETY: setsid and ioctl are async-signal-safe.
+            unsafe {
+                child.pre_exec(|| {
+                    // A new session whose controlling terminal is the pseudo-terminal, so job
+                    // control and Ctrl-C reach the command rather than `task`.
+                    check(libc::setsid())?;
+                    check(libc::ioctl(0, libc::TIOCSCTTY as _, 0))
+                });
+            }
+            child.spawn().context("Failed to execute command")?
+        };
+
+        // The session leader's pid is also its process group.
+        let mut supervisor = Supervisor::new(&child, timeout);
+        let mut master = File::from(master);
+        let result = relay(&mut master, &mut child, &mut supervisor, &mut size);
+        drop(raw_mode);
+        result?;
+        let status = child.wait().context("Failed to wait for command")?;
+        Ok(supervisor.finish(status))
+    }
+
+    /// Copy keystrokes to the command and its output to the screen until it closes the terminal.
+    fn relay(
+        master: &mut File,
+        child: &mut Child,
+        supervisor: &mut Supervisor,
+        size: &mut Option<libc::winsize>,
+    ) -> Result<()> {
+        let mut buffer = [0u8; 8192];
+        let mut stdin_open = true;
+        let mut stdout = io::stdout();
+
+        loop {
+            supervisor.tick(child);
+            let current = window_size();
+            if let Some(current) = current
+                && size.is_none_or(|size| {
+                    (size.ws_row, size.ws_col) != (current.ws_row, current.ws_col)
+                })
+            {
+                // SAFETY: `current` is a valid winsize for the duration of the call.
+                unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ as _, &current) };
+                *size = Some(current);
+            }
+
+            let mut fds = [
+                libc::pollfd {
+                    fd: if stdin_open { libc::STDIN_FILENO } else { -1 },
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: master.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            // SAFETY: `fds` is a valid array of two pollfd structs.
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), 2, POLL_INTERVAL_MS) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err).context("Failed to wait for terminal input");
+            }
+            if ready == 0 {
+                // A background process may hold the terminal open after the command is done.
+                if child.try_wait()?.is_some() {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            if fds[1].revents != 0 {
+                match master.read(&mut buffer) {
+                    // Linux reports EIO once every process closed the terminal side.
+                    Ok(0) | Err(_) => return Ok(()),
+                    Ok(read) => {
+                        stdout.write_all(&buffer[..read])?;
+                        stdout.flush()?;
+                    }
+                }
+            }
+
+            if fds[0].revents != 0 {
+                // SAFETY: `buffer` is valid for writes of its length.
+                let read = unsafe {
+                    libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len())
+                };
+                if read <= 0 {
+                    stdin_open = false;
+                } else {
+                    master.write_all(&buffer[..read as usize])?;
+                }
+            }
+        }
+    }
+
+    fn open_pty(
+        termios: Option<libc::termios>,
+        size: Option<libc::winsize>,
+    ) -> io::Result<(OwnedFd, OwnedFd)> {
+        let (mut master, mut slave) = (-1, -1);
+        let mut termios = termios;
+        let mut size = size;
+        // SAFETY: the out-pointers are valid, and the optional settings live for the call.
+        check(unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                termios
+                    .as_mut()
+                    .map_or(ptr::null_mut(), |termios| termios as *mut _),
+                size.as_mut().map_or(ptr::null_mut(), |size| size as *mut _),
+            )
+        })?;
+        // SAFETY: openpty succeeded, so both descriptors are open and owned by nobody else.
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        for fd in [&master, &slave] {
+            // SAFETY: `fd` is open.
+            check(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+        }
+        Ok((master, slave))
+    }
+
+    fn window_size() -> Option<libc::winsize> {
+        // SAFETY: winsize is plain data, and TIOCGWINSZ only writes to it.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let found = [libc::STDOUT_FILENO, libc::STDIN_FILENO]
+            .into_iter()
+            .any(|fd| unsafe { libc::ioctl(fd, libc::TIOCGWINSZ as _, &mut size) } == 0);
+        found.then_some(size)
+    }
+
+    /// This terminal switched to raw mode; the original settings come back when dropped.
+    struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+        /// `None` when stdin is not a terminal.
+        fn enter() -> Option<Self> {
+            // SAFETY: termios is plain data filled in by tcgetattr.
+            let mut original: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+                return None;
+            }
+            let mut raw = original;
+            // SAFETY: `raw` is a valid termios.
+            unsafe {
+                libc::cfmakeraw(&mut raw);
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+            }
+            Some(RawMode { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: `original` came from tcgetattr on the same descriptor.
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original) };
+        }
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_interactive_commands() {
+        for command in [
+            "htop",
+            "git rebase -i HEAD~3",
+            "git add -p",
+            "git commit",
+            "less /var/log/syslog",
+            "sudo vim /etc/hosts",
+            "python3",
+            "psql mydb",
+            "ssh prod-1",
+            "ps aux | less",
+        ] {
+            assert!(needs_terminal(command), "{command}");
+        }
+        for command in [
+            "ls -la",
+            "git commit -m 'fix'",
+            "git log --oneline",
+            "python3 script.py",
+            "psql mydb -c 'select 1'",
+            "ssh prod-1 uptime",
+            "echo vim",
+        ] {
+            assert!(!needs_terminal(command), "{command}");
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Calls that hand a string to the shell, or run an argument list, in Python, Perl, Ruby and Node.
+static CALLS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:system|popen|Popen|getoutput|getstatusoutput|check_output|check_call|call|run|exec|execSync|execFileSync|spawn|spawnSync|execv|execvp)\b\s*\(?\s*",
+    )
+    .expect("valid regex")
+});
+
+/// A string literal in any of the quoting styles the interpreters share.
+static LITERAL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"^(?:"((?:[^"\\]|\\.)*)"|'((?:[^'\\]|\\.)*)'|`([^`]*)`|qq?\{([^}]*)\}|qq?\(([^)]*)\))"#,
+    )
+    .expect("valid regex")
+});
+
+/// Backticks, `qx{}` and `%x()`, which run their contents through the shell.
+static CAPTURES: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"`([^`]*)`|\bqx\{([^}]*)\}|\bqx\(([^)]*)\)|%x\{([^}]*)\}|%x\(([^)]*)\)")
+        .expect("valid regex")
+});
+
+/// Shell commands a Python, Perl, Ruby or Node one-liner runs, as far as they are spelled out.
+///
+/// `os.system('rm -rf /')`, `system(q{reboot})`, `subprocess.run(['rm', '-rf', '/'])` and
+/// backticks all yield the command; list arguments are joined with spaces.
+pub(super) fn shell_commands(script: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    for call in CALLS.find_iter(script) {
+        let rest = &script[call.end()..];
+        if let Some(list) = rest.strip_prefix('[') {
+            let items = &list[..list.find(']').unwrap_or(list.len())];
+            let words: Vec<String> = items
+                .split(',')
+                .filter_map(|item| literal(item.trim()))
+                .collect();
+            if !words.is_empty() {
+                commands.push(words.join(" "));
+            }
+        } else if let Some(command) = literal(rest) {
+            commands.push(command);
+        }
+    }
+
+    for captures in CAPTURES.captures_iter(script) {
+        if let Some(command) = captures.iter().skip(1).flatten().next() {
+            commands.push(command.as_str().to_string());
+        }
+    }
+
+    commands
+}
+
+/// The contents of the string literal `text` starts with.
+fn literal(text: &str) -> Option<String> {
+    let captures = LITERAL.captures(text)?;
+    let body = captures.iter().skip(1).flatten().next()?.as_str();
+    Some(body.replace("\\\"", "\"").replace("\\'", "'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_commands_in_one_liners() {
+        let cases: &[(&str, &[&str])] = &[
+            ("import os; os.system('rm -rf /')", &["rm -rf /"]),
+            ("system(q{rm -rf /})", &["rm -rf /"]),
+            (
+                "import subprocess; subprocess.run(['rm', '-rf', '/'])",
+                &["rm -rf /"],
+            ),
+            ("require('child_process').execSync(\"reboot\")", &["reboot"]),
+            ("puts `ls -la`", &["ls -la"]),
+            ("print(sum(range(10)))", &[]),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(shell_commands(script), *expected, "{script}");
+        }
+    }
+}
@@ -0,0 +1,765 @@
+use anyhow::{Result, anyhow, bail};
+
+/// A shell word after quote removal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    /// Literal text with quotes removed; expansions and substitutions are kept verbatim.
+    pub text: String,
+    /// The value depends on an expansion or substitution evaluated at runtime.
+    pub dynamic: bool,
+    /// Sources of the command and process substitutions inside the word.
+    pub substitutions: Vec<String>,
+}
+
+impl Word {
+    /// The word's text when it is fully known before running, e.g. a command name.
+    pub fn literal(&self) -> Option<&str> {
+        (!self.dynamic).then_some(self.text.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub op: &'static str,
+    /// The file or here-string; for `<<` and `<<-`, the heredoc body.
+    pub target: Word,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Simple(SimpleCommand),
+    /// A `( ... )` subshell or `{ ...; }` group, with redirections applied to the whole group.
+    Group {
+        body: Vec<Pipeline>,
+        redirects: Vec<Redirect>,
+    },
+    Function {
+        name: String,
+        body: Box<Node>,
+    },
+}
+
+/// Commands connected by `|`, in order.
+pub type Pipeline = Vec<Node>;
+
+/// Parse a command line into its pipelines.
+///
+/// Covers the grammar generated commands use: quoting, expansions, command and process
+/// substitution, pipelines, lists, groups, function definitions, redirections and heredocs.
+/// Keywords such as `if` or `do` are left in place as ordinary words.
+pub fn parse(source: &str) -> Result<Vec<Pipeline>> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    parser.parse_list(None)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(Word),
+    Op(&'static str),
+}
+
+/// Operators, longest first so that prefixes do not shadow them.
+const OPERATORS: &[&str] = &[
+    "&>>", "<<<", "<<-", "&&", "||", ";;", "|&", "&>", "<<", ">>", ">&", "<&", ">|", "<>", "|",
+    "&", ";", "<", ">", "(", ")",
+];
+
+const REDIRECTS: &[&str] = &[
+    "&>>", "<<<", "<<-", "&>", "<<", ">>", ">&", "<&", ">|", "<>", "<", ">",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        pos: 0,
+    };
+    let mut tokens = Vec::new();
+    let mut heredocs: Vec<Heredoc> = Vec::new();
+    let mut expect_delimiter: Option<bool> = None;
+
+    while let Some(ch) = lexer.peek() {
+        match ch {
+            ' ' | '\t' | '\r' => lexer.pos += 1,
+            '\\' if lexer.peek_at(1) == Some('\n') => lexer.pos += 2,
+            '#' => lexer.skip_line(),
+            '\n' => {
+                lexer.pos += 1;
+                tokens.push(Token::Op("\n"));
+                for heredoc in heredocs.drain(..) {
+                    let body = lexer.read_heredoc(&heredoc.delimiter, heredoc.strip_tabs);
+                    // The body stands in for the delimiter as the redirection target; an
+                    // unquoted delimiter leaves expansions in it live.
+                    tokens[heredoc.token] = Token::Word(Word {
+                        dynamic: !heredoc.quoted && body.contains(['$', '`']),
+                        text: body,
+                        substitutions: Vec::new(),
+                    });
+                }
+            }
+            '<' | '>' if lexer.peek_at(1) == Some('(') => {
+                tokens.push(Token::Word(lexer.read_word()?));
+            }
+            _ => {
+                if let Some(op) = lexer.read_operator() {
+                    if op == "<<" || op == "<<-" {
+                        expect_delimiter = Some(op == "<<-");
+                    }
+                    tokens.push(Token::Op(op));
+                    continue;
+                }
+
+                let start = lexer.pos;
+                let word = lexer.read_word()?;
+                // `2>&1`: a bare number directly before a redirection is its file descriptor.
+                let is_fd = lexer.chars[start..lexer.pos]
+                    .iter()
+                    .all(char::is_ascii_digit)
+                    && matches!(lexer.peek(), Some('<' | '>'));
+                if is_fd {
+                    continue;
+                }
+                if let Some(strip_tabs) = expect_delimiter.take() {
+                    heredocs.push(Heredoc {
+                        token: tokens.len(),
+                        delimiter: word.text.clone(),
+                        strip_tabs,
+                        quoted: lexer.chars[start..lexer.pos]
+                            .iter()
+                            .any(|ch| matches!(ch, '\'' | '"' | '\\')),
+                    });
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A heredoc whose body starts after the next newline.
+struct Heredoc {
+    /// Index of the delimiter token, replaced by the body once it is read.
+    token: usize,
+    delimiter: String,
+    strip_tabs: bool,
+    quoted: bool,
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        if ch.is_some() {
+            self.pos += 1;
+        }
+        ch
+    }
+
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|ch| ch != '\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// Read a heredoc body up to and including its delimiter line, returning the body.
+    fn read_heredoc(&mut self, delimiter: &str, strip_tabs: bool) -> String {
+        let mut body = String::new();
+        while self.peek().is_some() {
+            let start = self.pos;
+            self.skip_line();
+            let line: String = self.chars[start..self.pos].iter().collect();
+            self.next();
+
+            let line = if strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line.as_str()
+            };
+            if line == delimiter {
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        body
+    }
+
+    fn read_operator(&mut self) -> Option<&'static str> {
+        let op = OPERATORS.iter().copied().find(|op| {
+            op.chars()
+                .enumerate()
+                .all(|(offset, ch)| self.peek_at(offset) == Some(ch))
+        })?;
+        self.pos += op.chars().count();
+        Some(op)
+    }
+
+    fn read_word(&mut self) -> Result<Word> {
+        let mut word = Word::default();
+
+        while let Some(ch) = self.peek() {
+            match ch {
+                ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | ')' => break,
+                '(' if word.text.ends_with('=') => {
+                    // Array assignment such as `files=(a b)`; the elements are data.
+                    self.pos += 1;
+                    let elements = self.read_balanced('(', ')')?;
+                    word.text.push('(');
+                    word.text.push_str(&elements);
+                    word.text.push(')');
+                }
+                '(' => break,
+                '<' | '>' if self.peek_at(1) == Some('(') => {
+                    self.pos += 2;
+                    let source = self.read_balanced('(', ')')?;
+                    word.text.push(ch);
+                    word.text.push('(');
+                    word.text.push_str(&source);
+                    word.text.push(')');
+                    word.dynamic = true;
+                    word.substitutions.push(source);
+                }
+                '<' | '>' => break,
+                '\\' => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some('\n') | None => {}
+                        Some(escaped) => word.text.push(escaped),
+                    }
+                }
+                '\'' => {
+                    self.pos += 1;
+                    loop {
+                        match self.next() {
+                            Some('\'') => break,
+                            Some(quoted) => word.text.push(quoted),
+                            None => bail!("unterminated single quote"),
+                        }
+                    }
+                }
+                '"' => {
+                    self.pos += 1;
+                    self.read_double_quoted(&mut word)?;
+                }
+                '$' => self.read_dollar(&mut word, false)?,
+                '`' => {
+                    self.pos += 1;
+                    self.read_backticks(&mut word)?;
+                }
+                _ => {
+                    word.text.push(ch);
+                    self.pos += 1;
+                }
+            }
+        }
+
+        Ok(word)
+    }
+
+    fn read_double_quoted(&mut self, word: &mut Word) -> Result<()> {
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(escaped @ ('$' | '`' | '"' | '\\')) => word.text.push(escaped),
+                        Some('\n') => {}
+                        Some(other) => {
+                            word.text.push('\\');
+                            word.text.push(other);
+                        }
+                        None => bail!("unterminated double quote"),
+                    }
+                }
+                Some('$') => self.read_dollar(word, true)?,
+                Some('`') => {
+                    self.pos += 1;
+                    self.read_backticks(word)?;
+                }
+                Some(other) => {
+                    word.text.push(other);
+                    self.pos += 1;
+                }
+                None => bail!("unterminated double quote"),
+            }
+        }
+    }
+
+    /// Read an expansion starting at `$`, keeping it verbatim in the word's text.
+    fn read_dollar(&mut self, word: &mut Word, quoted: bool) -> Result<()> {
+        self.pos += 1;
+
+        match self.peek() {
+            Some('(') if self.peek_at(1) == Some('(') => {
+                self.pos += 1;
+                let expression = self.read_balanced('(', ')')?;
+                word.text.push_str(&format!("$({expression})"));
+                word.dynamic = true;
+            }
+            Some('(') => {
+                self.pos += 1;
+                let source = self.read_balanced('(', ')')?;
+                word.text.push_str(&format!("$({source})"));
+                word.dynamic = true;
+                word.substitutions.push(source);
+            }
+            Some('{') => {
+                self.pos += 1;
+                let expansion = self.read_balanced('{', '}')?;
+                word.text.push_str(&format!("${{{expansion}}}"));
+                word.dynamic = true;
+            }
+            Some('\'') if !quoted => {
+                self.pos += 1;
+                self.read_ansi_c(word)?;
+            }
+            Some(ch) if ch.is_ascii_alphanumeric() || ch == '_' => {
+                word.text.push('$');
+                while let Some(ch) = self
+                    .peek()
+                    .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '_')
+                {
+                    word.text.push(ch);
+                    self.pos += 1;
+                }
+                word.dynamic = true;
+            }
+            Some(ch @ ('@' | '*' | '#' | '?' | '$' | '!' | '-')) => {
+                word.text.push('$');
+                word.text.push(ch);
+                self.pos += 1;
+                word.dynamic = true;
+            }
+            _ => word.text.push('$'),
+        }
+
+        Ok(())
+    }
+
+    /// Read a `$'...'` string, decoding its backslash escapes as bash does.
+    ///
+    /// An escape that cannot be decoded makes the word dynamic, so a command name spelled with it
+    /// is treated as unknown rather than as whatever text was left.
+    fn read_ansi_c(&mut self, word: &mut Word) -> Result<()> {
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(()),
+                Some('\\') => {
+                    let Some(escaped) = self.next() else {
+                        bail!("unterminated $'...' string");
+                    };
+                    let decoded = match escaped {
+                        'a' => Some('\x07'),
+                        'b' => Some('\x08'),
+                        'e' | 'E' => Some('\x1b'),
+                        'f' => Some('\x0c'),
+                        'n' => Some('\n'),
+                        'r' => Some('\r'),
+                        't' => Some('\t'),
+                        'v' => Some('\x0b'),
+                        '\\' | '\'' | '"' | '?' => Some(escaped),
+                        'x' => self.read_code(16, 2),
+                        'u' => self.read_code(16, 4),
+                        'U' => self.read_code(16, 8),
+                        '0'..='7' => {
+                            self.pos -= 1;
+                            self.read_code(8, 3)
+                        }
+                        'c' => self
+                            .next()
+                            .filter(char::is_ascii)
+                            .map(|control| char::from(control.to_ascii_uppercase() as u8 & 0x1f)),
+                        _ => None,
+                    };
+                    match decoded {
+                        Some(ch) => word.text.push(ch),
+                        None => {
+                            word.text.push('\\');
+                            word.text.push(escaped);
+                            word.dynamic = true;
+                        }
+                    }
+                }
+                Some(ch) => word.text.push(ch),
+                None => bail!("unterminated $'...' string"),
+            }
+        }
+    }
+
+    /// Read up to `max` digits in `radix` as a character code; `None` without digits or when the
+    /// code is not a character.
+    fn read_code(&mut self, radix: u32, max: usize) -> Option<char> {
+        let mut code = 0;
+        let mut digits = 0;
+        while digits < max
+            && let Some(digit) = self.peek().and_then(|ch| ch.to_digit(radix))
+        {
+            code = code * radix + digit;
+            digits += 1;
+            self.pos += 1;
+        }
+        if digits == 0 {
+            return None;
+        }
+        char::from_u32(code)
+    }
+
+    fn read_backticks(&mut self, word: &mut Word) -> Result<()> {
+        let mut source = String::new();
+        loop {
+            match self.next() {
+                Some('`') => break,
+                Some('\\') => match self.next() {
+                    Some(escaped @ ('`' | '\\' | '$')) => source.push(escaped),
+                    Some(other) => {
+                        source.push('\\');
+                        source.push(other);
+                    }
+                    None => bail!("unterminated backquote"),
+                },
+                Some(ch) => source.push(ch),
+                None => bail!("unterminated backquote"),
+            }
+        }
+
+        word.text.push_str(&format!("`{source}`"));
+        word.dynamic = true;
+        word.substitutions.push(source);
+        Ok(())
+    }
+
+    /// Read up to the delimiter closing an already consumed `open`, skipping quoted text.
+    fn read_balanced(&mut self, open: char, close: char) -> Result<String> {
+        let mut depth = 1;
+        let mut source = String::new();
+
+        loop {
+            let ch = self
+                .next()
+                .ok_or_else(|| anyhow!("missing closing `{close}`"))?;
+            match ch {
+                '\\' => {
+                    source.push(ch);
+                    if let Some(escaped) = self.next() {
+                        source.push(escaped);
+                    }
+                    continue;
+                }
+                '\'' | '"' => {
+                    source.push(ch);
+                    loop {
+                        let quoted = self.next().ok_or_else(|| anyhow!("unterminated quote"))?;
+                        source.push(quoted);
+                        if quoted == '\\' && ch == '"' {
+                            if let Some(escaped) = self.next() {
+                                source.push(escaped);
+                            }
+                        } else if quoted == ch {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                _ if ch == open => depth += 1,
+                _ if ch == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(source);
+                    }
+                }
+                _ => {}
+            }
+            source.push(ch);
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn at_word(&self, text: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.literal() == Some(text))
+    }
+
+    /// Parse pipelines up to `end` (`)` or `}`), or to the end of input.
+    fn parse_list(&mut self, end: Option<&'static str>) -> Result<Vec<Pipeline>> {
+        let mut pipelines = Vec::new();
+
+        loop {
+            match self.peek() {
+                None if end == Some(")") => bail!("missing closing `)`"),
+                None => return Ok(pipelines),
+                Some(Token::Op(")")) if end == Some(")") => {
+                    self.pos += 1;
+                    return Ok(pipelines);
+                }
+                Some(Token::Word(_)) if end == Some("}") && self.at_word("}") => {
+                    self.pos += 1;
+                    return Ok(pipelines);
+                }
+                // An unmatched `)` closes a `case` pattern; the pattern itself is data.
+                Some(Token::Op(";" | "\n" | "&" | "&&" | "||" | ";;" | ")")) => self.pos += 1,
+                _ => pipelines.push(self.parse_pipeline()?),
+            }
+        }
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline> {
+        let mut stages = vec![self.parse_command()?];
+
+        while matches!(self.peek(), Some(Token::Op("|" | "|&"))) {
+            self.pos += 1;
+            while matches!(self.peek(), Some(Token::Op("\n"))) {
+                self.pos += 1;
+            }
+            stages.push(self.parse_command()?);
+        }
+
+        Ok(stages)
+    }
+
+    fn parse_command(&mut self) -> Result<Node> {
+        if matches!(self.peek(), Some(Token::Op("("))) {
+            self.pos += 1;
+            let body = self.parse_list(Some(")"))?;
+            let redirects = self.parse_redirects()?;
+            return Ok(Node::Group { body, redirects });
+        }
+
+        if self.at_word("{") {
+            self.pos += 1;
+            let body = self.parse_list(Some("}"))?;
+            let redirects = self.parse_redirects()?;
+            return Ok(Node::Group { body, redirects });
+        }
+
+        if self.at_word("function")
+            && let Some(Token::Word(name)) = self.peek_at(1)
+        {
+            let name = name.text.clone();
+            self.pos += 2;
+            if matches!(self.peek(), Some(Token::Op("(")))
+                && matches!(self.peek_at(1), Some(Token::Op(")")))
+            {
+                self.pos += 2;
+            }
+            return self.parse_function_body(name);
+        }
+
+        let mut command = SimpleCommand::default();
+        loop {
+            match self.peek() {
+                Some(Token::Word(word)) => {
+                    command.words.push(word.clone());
+                    self.pos += 1;
+                }
+                Some(Token::Op(op)) if REDIRECTS.contains(op) => {
+                    command.redirects.push(self.parse_redirect()?);
+                }
+                Some(Token::Op("("))
+                    if command.words.len() == 1
+                        && command.redirects.is_empty()
+                        && matches!(self.peek_at(1), Some(Token::Op(")"))) =>
+                {
+                    self.pos += 2;
+                    let name = command.words.remove(0).text;
+                    return self.parse_function_body(name);
+                }
+                _ => break,
+            }
+        }
+
+        if command.words.is_empty() && command.redirects.is_empty() {
+            return Err(match self.peek() {
+                Some(Token::Op(op)) => anyhow!("unexpected `{}`", op.escape_default()),
+                _ => anyhow!("expected a command"),
+            });
+        }
+
+        Ok(Node::Simple(command))
+    }
+
+    fn parse_function_body(&mut self, name: String) -> Result<Node> {
+        while matches!(self.peek(), Some(Token::Op("\n"))) {
+            self.pos += 1;
+        }
+        let body = self.parse_command()?;
+        Ok(Node::Function {
+            name,
+            body: Box::new(body),
+        })
+    }
+
+    fn parse_redirects(&mut self) -> Result<Vec<Redirect>> {
+        let mut redirects = Vec::new();
+        while matches!(self.peek(), Some(Token::Op(op)) if REDIRECTS.contains(op)) {
+            redirects.push(self.parse_redirect()?);
+        }
+        Ok(redirects)
+    }
+
+    fn parse_redirect(&mut self) -> Result<Redirect> {
+        let Some(Token::Op(op)) = self.peek().cloned() else {
+            bail!("expected a redirection");
+        };
+        self.pos += 1;
+
+        match self.peek() {
+            Some(Token::Word(target)) => {
+                let target = target.clone();
+                self.pos += 1;
+                Ok(Redirect { op, target })
+            }
+            _ => bail!("redirection `{op}` is missing its target"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(node: &Node) -> Vec<&str> {
+        match node {
+            Node::Simple(command) => command
+                .words
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect(),
+            _ => panic!("expected a simple command, got {node:?}"),
+        }
+    }
+
+    #[test]
+    fn removes_quotes_and_keeps_expansions() {
+        let parsed = parse(r#"r''m -rf "$HOME/tmp" 'a b' c\ d"#).unwrap();
+        assert_eq!(
+            words(&parsed[0][0]),
+            ["rm", "-rf", "$HOME/tmp", "a b", "c d"]
+        );
+
+        let Node::Simple(command) = &parsed[0][0] else {
+            unreachable!()
+        };
+        assert!(command.words[2].dynamic);
+        assert!(!command.words[3].dynamic);
+    }
+
+    #[test]
+    fn decodes_ansi_c_escapes() {
+        let parsed = parse(r"$'\x72m' $'\162m' $'\u0072m' $'\U00000072m' $'a\tb' $'\cA'").unwrap();
+        assert_eq!(
+            words(&parsed[0][0]),
+            ["rm", "rm", "rm", "rm", "a\tb", "\u{1}"]
+        );
+
+        let parsed = parse(r"$'\qrm' -rf /").unwrap();
+        let Node::Simple(command) = &parsed[0][0] else {
+            unreachable!()
+        };
+        assert!(
+            command.words[0].dynamic,
+            "undecodable escapes are not trusted"
+        );
+    }
+
+    #[test]
+    fn collects_substitutions_and_redirections() {
+        let parsed = parse("echo $(date) `whoami` > out.txt 2>&1").unwrap();
+        let Node::Simple(command) = &parsed[0][0] else {
+            unreachable!()
+        };
+
+        assert_eq!(command.words[1].substitutions, ["date"]);
+        assert_eq!(command.words[2].substitutions, ["whoami"]);
+        let targets: Vec<(&str, &str)> = command
+            .redirects
+            .iter()
+            .map(|redirect| (redirect.op, redirect.target.text.as_str()))
+            .collect();
+        assert_eq!(targets, [(">", "out.txt"), (">&", "1")]);
+    }
+
+    #[test]
+    fn parses_pipelines_lists_and_groups() {
+        let parsed = parse("(cd /tmp && ls) | wc -l; { echo a; echo b; } > log").unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].len(), 2);
+        assert!(matches!(&parsed[0][0], Node::Group { body, .. } if body.len() == 2));
+        assert!(matches!(&parsed[1][0], Node::Group { redirects, .. } if redirects.len() == 1));
+    }
+
+    #[test]
+    fn parses_function_definitions() {
+        let parsed = parse(":(){ :|:& };:").unwrap();
+
+        assert!(matches!(&parsed[0][0], Node::Function { name, .. } if name == ":"));
+        assert_eq!(words(&parsed[1][0]), [":"]);
+    }
+
+    #[test]
+    fn skips_comments_and_heredoc_bodies() {
+        let parsed = parse("cat <<'EOF' # banner\nrm -rf /\nEOF\necho done").unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(words(&parsed[1][0]), ["echo", "done"]);
+    }
+
+    #[test]
+    fn keeps_heredoc_bodies_as_redirect_targets() {
+        let parsed = parse("sh <<'EOF'\nrm -rf ~\nEOF\ncat <<-END\n\techo $HOME\n\tEND").unwrap();
+
+        let Node::Simple(quoted) = &parsed[0][0] else {
+            panic!("expected a simple command");
+        };
+        assert_eq!(quoted.redirects[0].op, "<<");
+        assert_eq!(quoted.redirects[0].target.literal(), Some("rm -rf ~\n"));
+
+        let Node::Simple(unquoted) = &parsed[1][0] else {
+            panic!("expected a simple command");
+        };
+        assert_eq!(unquoted.redirects[0].target.text, "echo $HOME\n");
+        assert!(unquoted.redirects[0].target.dynamic);
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert!(parse("echo 'oops").is_err());
+        assert!(parse("echo $(date").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+use super::parser::Word;
+use super::{
+    DOWNLOADERS, FORMAT_COMMANDS, POWER_COMMANDS, has_long_flag, has_short_flag, is_protected_path,
+};
+
+/// What running a command can do, from least to most severe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RiskLevel {
+    #[default]
+    ReadOnly,
+    NetworkEgress,
+    ModifiesFiles,
+    Destructive,
+    Privileged,
+}
+
+impl RiskLevel {
+    /// One sentence describing the level, for explanations shown before running a command.
+    pub fn describe(self) -> &'static str {
+        match self {
+            RiskLevel::ReadOnly => "Only reads data.",
+            RiskLevel::NetworkEgress => "Sends or fetches data over the network.",
+            RiskLevel::ModifiesFiles => "Creates or changes files.",
+            RiskLevel::Destructive => "Deletes or overwrites data, which cannot be undone.",
+            RiskLevel::Privileged => "Runs with elevated privileges.",
+        }
+    }
+}
+
+impl fmt::Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RiskLevel::ReadOnly => "read-only",
+            RiskLevel::NetworkEgress => "network egress",
+            RiskLevel::ModifiesFiles => "modifies files",
+            RiskLevel::Destructive => "destructive",
+            RiskLevel::Privileged => "privileged",
+        })
+    }
+}
+
+/// Commands that only read files or report on the system.
+const READ_ONLY: &[&str] = &[
+    "[",
+    "basename",
+    "cal",
+    "cat",
+    "cd",
+    "cmp",
+    "column",
+    "comm",
+    "cut",
+    "date",
+    "df",
+    "diff",
+    "dirname",
+    "du",
+    "echo",
+    "expr",
+    "false",
+    "fd",
+    "file",
+    "free",
+    "grep",
+    "egrep",
+    "fgrep",
+    "head",
+    "hostname",
+    "id",
+    "jq",
+    "journalctl",
+    "less",
+    "ls",
+    "lsblk",
+    "lsof",
+    "man",
+    "md5sum",
+    "more",
+    "nl",
+    "od",
+    "paste",
+    "printenv",
+    "printf",
+    "ps",
+    "pwd",
+    "readlink",
+    "realpath",
+    "rev",
+    "rg",
+    "seq",
+    "sha1sum",
+    "sha256sum",
+    "sleep",
+    "sort",
+    "stat",
+    "tac",
+    "tail",
+    "test",
+    "top",
+    "tr",
+    "tree",
+    "true",
+    "type",
+    "uname",
+    "uniq",
+    "uptime",
+    "wc",
+    "which",
+    "whoami",
+    "xxd",
+    "yq",
+];
+
+/// Commands whose purpose is talking to other machines.
+const NETWORK: &[&str] = &[
+    "dig",
+    "ftp",
+    "host",
+    "nc",
+    "ncat",
+    "netcat",
+    "nslookup",
+    "ping",
+    "sftp",
+    "ssh",
+    "telnet",
+    "traceroute",
+];
+
+/// Commands that delete or irreversibly overwrite data.
+const DESTRUCTIVE: &[&str] = &[
+    "rm", "rmdir", "shred", "unlink", "dd", "truncate", "kill", "pkill", "killall",
+];
+
+/// Commands whose operands are all files they create, change or delete.
+const FILE_OPERANDS: &[&str] = &[
+    "cp", "ln", "mkdir", "mv", "rm", "rmdir", "shred", "tee", "touch", "truncate", "unlink",
+];
+
+/// Commands whose first operand is a mode or owner rather than a file.
+const ATTRIBUTE_COMMANDS: &[&str] = &["chmod", "chown", "chgrp"];
+
+/// Read-only `git` subcommands; the network ones are listed separately.
+const GIT_READ_ONLY: &[&str] = &[
+    "blame",
+    "branch",
+    "describe",
+    "diff",
+    "grep",
+    "log",
+    "ls-files",
+    "reflog",
+    "rev-parse",
+    "shortlog",
+    "show",
+    "status",
+    "tag",
+];
+const GIT_NETWORK: &[&str] = &["clone", "fetch", "ls-remote", "pull", "push"];
+
+/// Classify a single command by its name and arguments.
+///
+/// Unknown commands count as modifying files, since nothing says they do not.
+pub(super) fn classify(name: &str, args: &[Word]) -> RiskLevel {
+    match name {
+        "find" if has_long_flag(args, "-delete") => RiskLevel::Destructive,
+        "find" => RiskLevel::ReadOnly,
+        "sed" | "perl" if in_place(args) => RiskLevel::ModifiesFiles,
+        "sed" => RiskLevel::ReadOnly,
+        "git" => git(args),
+        // Without a command to run, which the checker unwraps, `env` only prints the environment.
+        "env" => RiskLevel::ReadOnly,
+        // Moving a home or system directory away breaks as much as deleting it.
+        "mv" if operands(args)
+            .split_last()
+            .is_some_and(|(_, sources)| sources.iter().any(|source| is_protected_path(source))) =>
+        {
+            RiskLevel::Destructive
+        }
+        _ if ATTRIBUTE_COMMANDS.contains(&name)
+            && (has_short_flag(args, 'R') || has_long_flag(args, "--recursive"))
+            && operands(args)
+                .iter()
+                .skip(1)
+                .any(|path| is_protected_path(path)) =>
+        {
+            RiskLevel::Destructive
+        }
+        "scp" | "rsync" if args.iter().any(|arg| is_remote(&arg.text)) => RiskLevel::NetworkEgress,
+        _ if DOWNLOADERS.contains(&name) => {
+            if has_short_flag(args, 'o') || has_short_flag(args, 'O') {
+                RiskLevel::ModifiesFiles
+            } else {
+                RiskLevel::NetworkEgress
+            }
+        }
+        _ if DESTRUCTIVE.contains(&name)
+            || POWER_COMMANDS.contains(&name)
+            || FORMAT_COMMANDS.contains(&name)
+            || name.starts_with("mkfs") =>
+        {
+            RiskLevel::Destructive
+        }
+        _ if NETWORK.contains(&name) => RiskLevel::NetworkEgress,
+        _ if READ_ONLY.contains(&name) => RiskLevel::ReadOnly,
+        _ => RiskLevel::ModifiesFiles,
+    }
+}
+
+fn git(args: &[Word]) -> RiskLevel {
+    let subcommand = args
+        .iter()
+        .find(|arg| !arg.text.starts_with('-'))
+        .map(|arg| arg.text.as_str());
+    match subcommand {
+        Some("push") if has_long_flag(args, "--force") || has_short_flag(args, 'f') => {
+            RiskLevel::Destructive
+        }
+        Some("reset") if has_long_flag(args, "--hard") => RiskLevel::Destructive,
+        Some("clean") => RiskLevel::Destructive,
+        Some(sub) if GIT_NETWORK.contains(&sub) => RiskLevel::NetworkEgress,
+        Some(sub) if GIT_READ_ONLY.contains(&sub) => RiskLevel::ReadOnly,
+        None => RiskLevel::ReadOnly,
+        Some(_) => RiskLevel::ModifiesFiles,
+    }
+}
+
+fn in_place(args: &[Word]) -> bool {
+    args.iter()
+        .take_while(|arg| arg.text != "--")
+        .any(|arg| arg.text.starts_with("-i") || arg.text.starts_with("--in-place"))
+}
+
+/// An `scp`/`rsync` operand such as `host:path` or `user@host:path`.
+fn is_remote(operand: &str) -> bool {
+    !operand.starts_with('-')
+        && operand
+            .split_once(':')
+            .is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'))
+}
+
+/// Files a command at `level` or above creates, changes or deletes.
+pub(super) fn affected_paths(name: &str, args: &[Word], level: RiskLevel) -> Vec<String> {
+    if level < RiskLevel::ModifiesFiles {
+        return Vec::new();
+    }
+
+    let operands = operands(args);
+
+    match name {
+        "dd" => operands
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("of="))
+            .map(str::to_string)
+            .collect(),
+        "find" => args
+            .iter()
+            .take_while(|arg| !arg.text.starts_with('-') && !matches!(arg.text.as_str(), "(" | "!"))
+            .map(|arg| arg.text.clone())
+            .collect(),
+        _ if FILE_OPERANDS.contains(&name) => operands.iter().map(|arg| arg.to_string()).collect(),
+        // The first operand is the script unless one was passed with `-e`.
+        "sed" | "perl" if !has_short_flag(args, 'e') => {
+            operands.iter().skip(1).map(|arg| arg.to_string()).collect()
+        }
+        _ if ATTRIBUTE_COMMANDS.contains(&name) => {
+            operands.iter().skip(1).map(|arg| arg.to_string()).collect()
+        }
+        _ => operands
+            .iter()
+            .filter(|arg| looks_like_path(arg))
+            .map(|arg| arg.to_string())
+            .collect(),
+    }
+}
+
+/// Arguments that are not options; a lone `-` stands for stdin or stdout.
+fn operands(args: &[Word]) -> Vec<&str> {
+    args.iter()
+        .map(|arg| arg.text.as_str())
+        .filter(|arg| !arg.starts_with('-') || *arg == "-")
+        .collect()
+}
+
+fn looks_like_path(operand: &str) -> bool {
+    !operand.contains("://")
+        && (operand.contains('/')
+            || operand.starts_with('.')
+            || operand.starts_with('~')
+            || operand.contains('*'))
+}
@@ -0,0 +1,570 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::safety;
+
+/// Commands that act on processes, services or the session rather than on files, which a sandbox
+/// cannot show or contain faithfully.
+const OUTSIDE_FILES: &[&str] = &[
+    "kill",
+    "pkill",
+    "killall",
+    "systemctl",
+    "service",
+    "launchctl",
+    "loginctl",
+    "docker",
+    "podman",
+    "kubectl",
+    "dbus-send",
+    "busctl",
+    "gdbus",
+    "shutdown",
+    "reboot",
+    "poweroff",
+    "halt",
+];
+
+/// How a dry run changed a file below the working directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Relative to the working directory; directories end with `/`.
+    pub path: String,
+}
+
+/// What a command did inside the sandbox.
+#[derive(Debug)]
+pub struct DryRun {
+    pub status: ExitStatus,
+    /// The command was stopped because it ran past its timeout.
+    pub timed_out: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub changes: Vec<Change>,
+}
+
+/// Run `command` with `shell` in a throwaway sandbox and report the files it would change.
+///
+/// The command runs in new user, mount, network, PID and IPC namespaces: the working directory
+/// is an overlay whose writes land in a scratch directory, every other mount is read-only, `/run`,
+/// `/tmp` and `$XDG_RUNTIME_DIR` are hidden so host sockets are out of reach, only an unconfigured
+/// loopback interface exists, and only the command's own processes can be signalled. Everything still running is killed once the
+/// command exits or `timeout` passes, and the scratch directory is removed afterwards.
+#[cfg(target_os = "linux")]
+pub fn dry_run(command: &str, shell: &str, timeout: Option<Duration>) -> Result<DryRun> {
+    linux::dry_run(command, shell, timeout)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn dry_run(_command: &str, _shell: &str, _timeout: Option<Duration>) -> Result<DryRun> {
+    anyhow::bail!("Dry runs need Linux user and mount namespaces")
+}
+
+/// Why `command` should not be dry-run, if its effects reach beyond files.
+///
+/// Signals, service managers and container daemons act on the live system, and privileged
+/// commands cannot run in the sandbox at all, so a dry run would misrepresent what they do.
+pub fn unsupported(command: &str) -> Option<String> {
+    let analysis = safety::analyze(command);
+    if analysis.level == safety::RiskLevel::Privileged {
+        return Some("it needs elevated privileges, which the sandbox does not grant".to_string());
+    }
+    analysis
+        .commands
+        .iter()
+        .filter_map(|argv| argv.first())
+        .map(|name| Path::new(name.trim_start_matches('^')))
+        .filter_map(|name| name.file_name()?.to_str())
+        .find(|name| OUTSIDE_FILES.contains(name))
+        .map(|name| format!("`{name}` acts on processes or services outside the working directory"))
+}
+
+/// Compare the overlay's upper directory with the lower one it shadows.
+///
+/// Whiteouts (character devices numbered 0:0) mark deletions; anything else in the upper
+/// directory was either created or copied up to be modified.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn collect_changes(upper: &Path, lower: &Path) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    walk(upper, lower, Path::new(""), &mut changes)?;
+    changes.sort();
+    Ok(changes)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn walk(upper: &Path, lower: &Path, relative: &Path, changes: &mut Vec<Change>) -> Result<()> {
+    for entry in fs::read_dir(upper.join(relative))? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        let existed = fs::symlink_metadata(lower.join(&relative)).is_ok();
+        let display = relative.display().to_string();
+
+        if is_whiteout(&metadata) {
+            changes.push(Change {
+                kind: ChangeKind::Deleted,
+                path: display,
+            });
+        } else if metadata.is_dir() {
+            if !existed {
+                changes.push(Change {
+                    kind: ChangeKind::Created,
+                    path: format!("{display}/"),
+                });
+            }
+            walk(upper, lower, &relative, changes)?;
+        } else {
+            changes.push(Change {
+                kind: if existed {
+                    ChangeKind::Modified
+                } else {
+                    ChangeKind::Created
+                },
+                path: display,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_whiteout(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    metadata.file_type().is_char_device() && metadata.rdev() == 0
+}
+
+#[cfg(not(unix))]
+fn is_whiteout(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Scratch space for the overlay, removed when dropped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        env,
+        ffi::CString,
+        fs,
+        io::{self, Read},
+        os::unix::{ffi::OsStrExt, process::CommandExt},
+        path::{Path, PathBuf},
+        process::{self, Command, Stdio},
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{Context, Result, anyhow};
+
+    use super::{DryRun, Scratch, collect_changes};
+    use crate::supervisor::Supervisor;
+
+    /// Where host services keep their sockets; covered with an empty tmpfs in the sandbox, as is
+    /// `$XDG_RUNTIME_DIR`.
+    const RUNTIME_DIRS: &[&str] = &["/run", "/var/run", "/tmp"];
+
+    /// `move_mount` flag to take the mount from the file descriptor itself.
+    const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+
+    pub fn dry_run(command: &str, shell: &str, timeout: Option<Duration>) -> Result<DryRun> {
+        let cwd = env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .context("Failed to resolve the working directory")?;
+        let scratch = Scratch(env::temp_dir().join(format!("task-sh-dry-run-{}", process::id())));
+        if scratch.0.starts_with(&cwd) {
+            return Err(anyhow!(
+                "Cannot dry-run from {}, which contains the sandbox scratch directory",
+                cwd.display()
+            ));
+        }
+        let upper = scratch.0.join("upper");
+        let work = scratch.0.join("work");
+        fs::create_dir_all(&upper)
+            .and_then(|()| fs::create_dir_all(&work))
+            .context("Failed to create the sandbox scratch directory")?;
+
+        // Everything the child needs is prepared here: only raw syscalls run after the fork.
+        let target = CString::new(cwd.as_os_str().as_bytes())?;
+        let options = CString::new(format!(
+            "lowerdir={},upperdir={},workdir={},userxattr",
+            escape(&cwd.to_string_lossy()),
+            escape(&upper.to_string_lossy()),
+            escape(&work.to_string_lossy())
+        ))?;
+        // SAFETY: getuid and getgid cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("{uid} {uid} 1");
+        let gid_map = format!("{gid} {gid} 1");
+        let hidden = runtime_dirs(&cwd);
+        // A working directory below a hidden one is rebuilt inside its tmpfs to mount the overlay
+        // back onto.
+        let rebuilt: Vec<CString> = match hidden.iter().find(|dir| cwd.starts_with(dir)) {
+            Some(dir) => {
+                let mut below: Vec<&Path> =
+                    cwd.ancestors().take_while(|path| path != dir).collect();
+                below.reverse();
+                below
+                    .into_iter()
+                    .map(|path| CString::new(path.as_os_str().as_bytes()))
+                    .collect::<Result<_, _>>()?
+            }
+            None => Vec::new(),
+        };
+        let hidden: Vec<CString> = hidden
+            .iter()
+            .map(|dir| CString::new(dir.as_os_str().as_bytes()))
+            .collect::<Result<_, _>>()?;
+
+        let mut child = Command::new(shell);
+        child
+            .arg("-c")
+            .arg(command)
+            .current_dir(&cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        // SAFETY: the closure only makes async-signal-safe syscalls on data prepared above.
+        unsafe {
+            child.pre_exec(move || {
+                enter_sandbox(&target, &options, &uid_map, &gid_map, &hidden, &rebuilt)
+            });
+        }
+
+        let mut child = child.spawn().map_err(|err| {
+            anyhow!(err).context(
+                "Failed to start the dry-run sandbox; it needs unprivileged user namespaces and overlayfs",
+            )
+        })?;
+        let mut stdout = child.stdout.take().context("Failed to capture stdout")?;
+        let mut stderr = child.stderr.take().context("Failed to capture stderr")?;
+
+        let mut supervisor = Supervisor::new(&child, timeout);
+        let (finished, stdout, stderr) = thread::scope(|scope| {
+            let stdout = scope.spawn(move || {
+                let mut buffer = Vec::new();
+                stdout.read_to_end(&mut buffer).map(|_| buffer)
+            });
+            let stderr = scope.spawn(move || {
+                let mut buffer = Vec::new();
+                stderr.read_to_end(&mut buffer).map(|_| buffer)
+            });
+            let finished = supervisor.wait(&mut child);
+            (finished, stdout.join(), stderr.join())
+        });
+        drop(supervisor);
+        let finished = finished?;
+        let join = |result: thread::Result<io::Result<Vec<u8>>>| {
+            result
+                .map_err(|_| anyhow!("Output reader panicked"))?
+                .context("Failed to read command output")
+        };
+
+        Ok(DryRun {
+            status: finished.status,
+            timed_out: finished.timed_out,
+            stdout: join(stdout)?,
+            stderr: join(stderr)?,
+            changes: collect_changes(&upper, &cwd)?,
+        })
+    }
+
+    /// The runtime directories present on this host, without ones nested in another or the
+    /// working directory itself.
+    fn runtime_dirs(cwd: &Path) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = RUNTIME_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .chain(env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from))
+            .filter(|dir| dir.is_absolute())
+            .filter_map(|dir| dir.canonicalize().ok())
+            .filter(|dir| dir.is_dir() && dir != cwd)
+            .collect();
+        // Parents sort before their children.
+        dirs.sort();
+        let mut outermost: Vec<PathBuf> = Vec::new();
+        for dir in dirs {
+            if !outermost.iter().any(|parent| dir.starts_with(parent)) {
+                outermost.push(dir);
+            }
+        }
+        outermost
+    }
+
+    /// Overlay options separate paths with `,` and `:`, so those need escaping.
+    fn escape(path: &str) -> String {
+        path.replace('\\', "\\\\")
+            .replace(',', "\\,")
+            .replace(':', "\\:")
+    }
+
+    fn enter_sandbox(
+        target: &CString,
+        options: &CString,
+        uid_map: &str,
+        gid_map: &str,
+        hidden: &[CString],
+        rebuilt: &[CString],
+    ) -> io::Result<()> {
+        check(unsafe {
+            libc::unshare(
+                libc::CLONE_NEWUSER
+                    | libc::CLONE_NEWNS
+                    | libc::CLONE_NEWNET
+                    | libc::CLONE_NEWPID
+                    | libc::CLONE_NEWIPC,
+            )
+        })?;
+        write_proc(c"/proc/self/setgroups", b"deny")?;
+        write_proc(c"/proc/self/uid_map", uid_map.as_bytes())?;
+        write_proc(c"/proc/self/gid_map", gid_map.as_bytes())?;
+
+        // Keep the mounts below from propagating back to the parent namespace.
+        check(unsafe {
+            libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            )
+        })?;
+        check(unsafe {
+            libc::mount(
+                c"overlay".as_ptr(),
+                target.as_ptr(),
+                c"overlay".as_ptr(),
+                0,
+                options.as_ptr().cast(),
+            )
+        })?;
+        // Keep hold of the overlay when the tmpfs below is about to cover it.
+        let overlay = if rebuilt.is_empty() {
+            None
+        } else {
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_open_tree,
+                    libc::AT_FDCWD,
+                    target.as_ptr(),
+                    libc::OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint,
+                )
+            };
+            check(fd as libc::c_int)?;
+            Some(fd as libc::c_int)
+        };
+        // Sockets of the Docker daemon, systemd and the session bus live below `/run`, those of
+        // X11 and SSH agents below `/tmp`.
+        for dir in hidden {
+            check(unsafe {
+                libc::mount(
+                    c"tmpfs".as_ptr(),
+                    dir.as_ptr(),
+                    c"tmpfs".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                    std::ptr::null(),
+                )
+            })?;
+        }
+
+        if let Some(fd) = overlay {
+            for dir in rebuilt {
+                check(unsafe { libc::mkdir(dir.as_ptr(), 0o755) })?;
+            }
+            check(unsafe {
+                libc::syscall(
+                    libc::SYS_move_mount,
+                    fd,
+                    c"".as_ptr(),
+                    libc::AT_FDCWD,
+                    target.as_ptr(),
+                    MOVE_MOUNT_F_EMPTY_PATH,
+                ) as libc::c_int
+            })?;
+            unsafe { libc::close(fd) };
+        }
+
+        // Everything outside the overlay becomes read-only.
+        set_mount_attr(c"/", libc::AT_RECURSIVE, libc::MOUNT_ATTR_RDONLY, 0)?;
+        set_mount_attr(target, 0, 0, libc::MOUNT_ATTR_RDONLY)?;
+
+        // The working directory still points below the overlay until it is entered again.
+        check(unsafe { libc::chdir(target.as_ptr()) })?;
+
+        enter_pid_namespace()
+    }
+
+    /// Fork so the command becomes PID 1 of the new PID namespace.
+    ///
+    /// The forked process goes on to run the command; this one stays behind, waits for it and
+    /// exits the same way, so the supervisor sees the command's status. Both share the process
+    /// group the supervisor signals, and should this process die first, the kernel kills the
+    /// command and with it the whole namespace.
+    fn enter_pid_namespace() -> io::Result<()> {
+        let pid = unsafe { libc::fork() };
+        check(pid)?;
+        if pid == 0 {
+            check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) })?;
+            // A `/proc` of its own keeps `ps` and `pkill` from even seeing host processes. Some
+            // container runtimes forbid the mount; the namespace alone still keeps them out of reach.
+            unsafe {
+                libc::mount(
+                    c"proc".as_ptr(),
+                    c"/proc".as_ptr(),
+                    c"proc".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                    std::ptr::null(),
+                )
+            };
+            return Ok(());
+        }
+
+        // Among the inherited descriptors is the pipe `spawn` watches until the command is
+        // executed; holding it open would make `spawn` wait for the command to finish.
+        if unsafe { libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) } < 0 {
+            for fd in 3..1024 {
+                unsafe { libc::close(fd) };
+            }
+        }
+
+        let mut status = 0;
+        while unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+            if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                unsafe { libc::_exit(127) };
+            }
+        }
+        unsafe {
+            if libc::WIFSIGNALED(status) {
+                let signal = libc::WTERMSIG(status);
+                libc::signal(signal, libc::SIG_DFL);
+                libc::kill(libc::getpid(), signal);
+                libc::_exit(128 + signal);
+            }
+            libc::_exit(libc::WEXITSTATUS(status))
+        }
+    }
+
+    fn set_mount_attr(
+        path: &std::ffi::CStr,
+        flags: libc::c_int,
+        set: u64,
+        clear: u64,
+    ) -> io::Result<()> {
+        let mut attr: libc::mount_attr = unsafe { std::mem::zeroed() };
+        attr.attr_set = set;
+        attr.attr_clr = clear;
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                flags as libc::c_uint,
+                &attr as *const libc::mount_attr,
+                std::mem::size_of::<libc::mount_attr>(),
+            )
+        };
+        check(result as libc::c_int)
+    }
+
+    fn write_proc(path: &std::ffi::CStr, contents: &[u8]) -> io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        check(fd)?;
+        let written = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+        unsafe { libc::close(fd) };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_created_modified_and_deleted_files() {
+        let lower = tempfile::tempdir().unwrap();
+        let upper = tempfile::tempdir().unwrap();
+        fs::write(lower.path().join("kept.txt"), "old").unwrap();
+        fs::create_dir(lower.path().join("src")).unwrap();
+        fs::write(upper.path().join("kept.txt"), "new").unwrap();
+        fs::create_dir_all(upper.path().join("src")).unwrap();
+        fs::create_dir_all(upper.path().join("out/logs")).unwrap();
+        fs::write(upper.path().join("out/logs/run.log"), "").unwrap();
+        // Whiteouts cannot be created unprivileged, so deletions are covered by the integration test.
+
+        let changes = collect_changes(upper.path(), lower.path()).unwrap();
+        let summary: Vec<(ChangeKind, &str)> = changes
+            .iter()
+            .map(|change| (change.kind, change.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (ChangeKind::Created, "out/"),
+                (ChangeKind::Created, "out/logs/"),
+                (ChangeKind::Created, "out/logs/run.log"),
+                (ChangeKind::Modified, "kept.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_commands_that_reach_beyond_files() {
+        for command in [
+            "kill -9 -1",
+            "pkill node",
+            "sudo rm -rf build",
+            "docker rm -f web",
+            "systemctl --user stop app",
+        ] {
+            assert!(unsupported(command).is_some(), "{command}");
+        }
+        for command in ["rm -rf build", "sed -i s/a/b/ notes.txt", "make clean"] {
+            assert_eq!(unsupported(command), None, "{command}");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+
+/// How long a timed-out command gets to exit after SIGTERM before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+/// How often a waiting supervisor checks on the command.
+const TICK: Duration = Duration::from_millis(20);
+
+/// Start `command` in its own process group.
+///
+/// When stdin is a terminal the group becomes its foreground group, so the command can read from
+/// it and gets Ctrl-C directly; [`Supervisor`] hands the terminal back when dropped.
+pub fn own_group(command: &mut Command) {
+    platform::own_group(command);
+}
+
+/// How a supervised command ended.
+#[derive(Debug, Clone, Copy)]
+pub struct Finished {
+    pub status: ExitStatus,
+    /// The command was stopped because it ran past its timeout.
+    pub timed_out: bool,
+}
+
+/// Watches a command running in its own process group.
+///
+/// While it exists, SIGINT and SIGTERM sent to `task` are passed on to the group instead of ending
+/// `task`, and the group is stopped once the timeout passes: SIGTERM first, SIGKILL if it lingers.
+/// Once a group that was interrupted or timed out has lost its leader, whatever is left of it is
+/// killed, so no stragglers are orphaned or keep the command's output open.
+pub struct Supervisor {
+    pgid: u32,
+    deadline: Option<Instant>,
+    terminated_at: Option<Instant>,
+    interrupted: bool,
+    _signals: platform::Forwarding,
+}
+
+impl Supervisor {
+    /// Supervise `child`, which must lead its own process group.
+    pub fn new(child: &Child, timeout: Option<Duration>) -> Self {
+        Supervisor {
+            pgid: child.id(),
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            terminated_at: None,
+            interrupted: false,
+            _signals: platform::Forwarding::install(),
+        }
+    }
+
+    /// Pass on pending signals and enforce the timeout; call this regularly while the command runs.
+    pub fn tick(&mut self, child: &mut Child) {
+        if let Some(signal) = platform::take_pending() {
+            platform::signal_group(self.pgid, child, signal);
+            self.interrupted = true;
+        }
+
+        let now = Instant::now();
+        match self.terminated_at {
+            None if self.deadline.is_some_and(|deadline| now >= deadline) => {
+                platform::terminate_group(self.pgid, child);
+                self.terminated_at = Some(now);
+            }
+            Some(terminated) if now >= terminated + KILL_GRACE => {
+                platform::kill_group(self.pgid, child);
+            }
+            _ => {}
+        }
+    }
+
+    /// Wait for `child` to exit, ticking in between.
+    pub fn wait(&mut self, child: &mut Child) -> Result<Finished> {
+        loop {
+            if let Some(status) = child.try_wait().context("Failed to wait for command")? {
+                return Ok(self.finish(status));
+            }
+            self.tick(child);
+            thread::sleep(TICK);
+        }
+    }
+
+    /// Describe how the command ended, given the status it exited with.
+    pub fn finish(&mut self, status: ExitStatus) -> Finished {
+        self.clean_up();
+        Finished {
+            status,
+            timed_out: self.terminated_at.is_some(),
+        }
+    }
+
+    fn clean_up(&self) {
+        if self.interrupted || self.terminated_at.is_some() {
+            platform::kill_remaining(self.pgid);
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.clean_up();
+        platform::reclaim_terminal();
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{
+        io,
+        os::unix::process::CommandExt,
+        process::{Child, Command},
+        sync::atomic::{AtomicI32, Ordering},
+    };
+
+    /// The last forwarded signal that has not been passed on yet; 0 when there is none.
+    static PENDING: AtomicI32 = AtomicI32::new(0);
+
+    const FORWARDED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+    extern "C" fn record(signal: libc::c_int) {
+        PENDING.store(signal, Ordering::SeqCst);
+    }
+
+    /// Handlers that record SIGINT and SIGTERM; the previous ones come back when dropped.
+    pub struct Forwarding {
+        previous: [libc::sigaction; 2],
+    }
+
+    impl Forwarding {
+        pub fn install() -> Self {
+            PENDING.store(0, Ordering::SeqCst);
+            // SAFETY: sigaction is plain data; `record` only touches an atomic.
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = record as *const () as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                let mut previous: [libc::sigaction; 2] = std::mem::zeroed();
+                for (signal, previous) in FORWARDED.iter().zip(previous.iter_mut()) {
+                    libc::sigaction(*signal, &action, previous);
+                }
+                Forwarding { previous }
+            }
+        }
+    }
+
+    impl Drop for Forwarding {
+        fn drop(&mut self) {
+            for (signal, previous) in FORWARDED.iter().zip(self.previous.iter()) {
+                // SAFETY: `previous` was filled in by sigaction for the same signal.
+                unsafe { libc::sigaction(*signal, previous, std::ptr::null_mut()) };
+            }
+        }
+    }
+
+    pub fn own_group(command: &mut Command) {
+        command.process_group(0);
+        // SAFETY: isatty has no memory-safety requirements.
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            // SAFETY: the closure only makes async-signal-safe calls.
+            unsafe {
+                command.pre_exec(|| {
+                    foreground(libc::getpid());
+                    Ok::<(), io::Error>(())
+                });
+            }
+        }
+    }
+
+    pub fn reclaim_terminal() {
+        // SAFETY: as above.
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            foreground(unsafe { libc::getpgrp() });
+        }
+    }
+
+    /// Make `pgid` the terminal's foreground group.
+    ///
+    /// Only the foreground group may do this without being stopped by SIGTTOU, unless the signal
+    /// is blocked, so it is blocked for the call.
+    fn foreground(pgid: libc::pid_t) {
+        // SAFETY: sigset_t is plain data initialised by sigemptyset; all calls are signal-safe.
+        unsafe {
+            let mut block: libc::sigset_t = std::mem::zeroed();
+            let mut previous: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut block);
+            libc::sigaddset(&mut block, libc::SIGTTOU);
+            libc::sigprocmask(libc::SIG_BLOCK, &block, &mut previous);
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+            libc::sigprocmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        }
+    }
+
+    pub fn take_pending() -> Option<libc::c_int> {
+        match PENDING.swap(0, Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    pub fn signal_group(pgid: u32, _child: &mut Child, signal: libc::c_int) {
+        // SAFETY: killpg has no memory-safety requirements; a vanished group yields ESRCH.
+        unsafe { libc::killpg(pgid as libc::pid_t, signal) };
+    }
+
+    pub fn terminate_group(pgid: u32, child: &mut Child) {
+        signal_group(pgid, child, libc::SIGTERM);
+    }
+
+    pub fn kill_group(pgid: u32, child: &mut Child) {
+        signal_group(pgid, child, libc::SIGKILL);
+    }
+
+    pub fn kill_remaining(pgid: u32) {
+        // SAFETY: as above.
+        unsafe { libc::killpg(pgid as libc::pid_t, libc::SIGKILL) };
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    use std::process::{Child, Command};
+
+    pub fn own_group(_command: &mut Command) {}
+
+    pub fn reclaim_terminal() {}
+
+    pub struct Forwarding;
+
+    impl Forwarding {
+        pub fn install() -> Self {
+            Forwarding
+        }
+    }
+
+    pub fn take_pending() -> Option<i32> {
+        None
+    }
+
+    pub fn signal_group(_pgid: u32, _child: &mut Child, _signal: i32) {}
+
+    pub fn terminate_group(_pgid: u32, child: &mut Child) {
+        let _ = child.kill();
+    }
+
+    pub fn kill_group(_pgid: u32, child: &mut Child) {
+        let _ = child.kill();
+    }
+
+    pub fn kill_remaining(_pgid: u32) {}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::Command;
+
+    use serial_test::serial;
+
+    use super::*;
+
+    #[test]
+    #[serial]
+    fn stops_the_whole_group_on_timeout() {
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+
+        let finished = Supervisor::new(&child, Some(Duration::from_millis(200)))
+            .wait(&mut child)
+            .unwrap();
+
+        assert!(finished.timed_out);
+        assert_eq!(finished.status.signal(), Some(libc::SIGTERM));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    #[serial]
+    fn forwards_signals_to_the_group() {
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut supervisor = Supervisor::new(&child, None);
+
+        // SAFETY: raising a signal the supervisor handles.
+        unsafe { libc::raise(libc::SIGINT) };
+        let finished = supervisor.wait(&mut child).unwrap();
+
+        assert!(!finished.timed_out);
+        assert_eq!(finished.status.signal(), Some(libc::SIGINT));
+    }
+}
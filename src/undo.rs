@@ -0,0 +1,464 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration as StdDuration, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::policy::glob_match;
+
+const MANIFEST: &str = "snapshot.toml";
+/// Snapshots kept at most; older ones are pruned when a new one is taken.
+const MAX_SNAPSHOTS: usize = 20;
+/// Snapshots older than this are pruned.
+const MAX_AGE_DAYS: i64 = 14;
+/// Commands touching more data than this run without a snapshot.
+const MAX_SNAPSHOT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// One path as it was before the command ran.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct Entry {
+    path: PathBuf,
+    /// Name of the copy inside the snapshot, or `None` if the path did not exist.
+    backup: Option<String>,
+}
+
+/// Copies of the paths a command touches, taken before it runs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: String,
+    pub command: String,
+    pub cwd: PathBuf,
+    /// Unix timestamp of when the snapshot was taken.
+    pub created: i64,
+    entries: Vec<Entry>,
+}
+
+impl Snapshot {
+    /// Paths that `task undo` would restore or remove.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().map(|entry| entry.path.as_path())
+    }
+}
+
+/// The outcome of `task undo`.
+#[derive(Debug)]
+pub struct Restored {
+    pub snapshot: Snapshot,
+    /// Paths that did not exist before the command but were left alone, because they are not a
+    /// file or directory created after the snapshot was taken.
+    pub kept: Vec<PathBuf>,
+}
+
+/// Snapshots stored below a data directory, by default `~/.local/share/task.sh/undo`.
+pub struct UndoStore {
+    root: PathBuf,
+}
+
+impl UndoStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            root: data_dir.join("undo"),
+        }
+    }
+
+    /// Back up `paths` before `command` runs, then prune old snapshots.
+    ///
+    /// `paths` are as written in the command: `~` and variables are expanded, relative paths are
+    /// resolved against the working directory, and wildcards in the last component are matched.
+    /// Returns `None` when there is nothing to back up.
+    pub fn snapshot(&self, command: &str, paths: &[String]) -> Result<Option<Snapshot>> {
+        let cwd = env::current_dir().context("Failed to resolve the working directory")?;
+        let targets = resolve_targets(&cwd, paths);
+        if targets.is_empty() {
+            return Ok(None);
+        }
+
+        // Copying `/` or the home directory is never feasible, so do not even measure them.
+        let home = dirs::home_dir();
+        if let Some(root) = targets
+            .iter()
+            .find(|path| path.parent().is_none() || home.as_ref() == Some(path))
+        {
+            return Err(anyhow!("The command affects {}", root.display()));
+        }
+
+        let mut size = 0;
+        for path in &targets {
+            size += disk_usage(path, MAX_SNAPSHOT_BYTES - size);
+            if size > MAX_SNAPSHOT_BYTES {
+                return Err(anyhow!(
+                    "The command touches more than the {} MiB snapshot limit",
+                    MAX_SNAPSHOT_BYTES / (1024 * 1024)
+                ));
+            }
+        }
+
+        let id = self.next_id();
+        let dir = self.root.join(&id);
+        fs::create_dir_all(dir.join("files"))
+            .with_context(|| format!("Failed to create snapshot directory {}", dir.display()))?;
+
+        let mut entries = Vec::new();
+        for (index, path) in targets.into_iter().enumerate() {
+            let backup = if fs::symlink_metadata(&path).is_ok() {
+                let name = index.to_string();
+                copy_tree(&path, &dir.join("files").join(&name))
+                    .with_context(|| format!("Failed to back up {}", path.display()))?;
+                Some(name)
+            } else {
+                None
+            };
+            entries.push(Entry { path, backup });
+        }
+
+        let snapshot = Snapshot {
+            id,
+            command: command.to_string(),
+            cwd,
+            created: Utc::now().timestamp(),
+            entries,
+        };
+        fs::write(dir.join(MANIFEST), toml::to_string(&snapshot)?)
+            .with_context(|| format!("Failed to write snapshot manifest in {}", dir.display()))?;
+
+        if let Err(err) = self.prune() {
+            warn!(error = %err, "Failed to prune old snapshots");
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// Every stored snapshot, oldest first.
+    pub fn list(&self) -> Result<Vec<Snapshot>> {
+        let Ok(dirs) = fs::read_dir(&self.root) else {
+            return Ok(Vec::new());
+        };
+
+        let mut snapshots = Vec::new();
+        for dir in dirs {
+            let manifest = dir?.path().join(MANIFEST);
+            if let Ok(contents) = fs::read_to_string(&manifest) {
+                let snapshot: Snapshot = toml::from_str(&contents)
+                    .with_context(|| format!("Failed to parse {}", manifest.display()))?;
+                snapshots.push(snapshot);
+            }
+        }
+        snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(snapshots)
+    }
+
+    /// Put back every path of snapshot `id`, or of the latest one, then delete the snapshot.
+    ///
+    /// Paths that did not exist before are removed only if they are now a regular file or
+    /// directory created after the snapshot; anything else there is kept and reported.
+    pub fn restore(&self, id: Option<&str>) -> Result<Restored> {
+        let snapshots = self.list()?;
+        let snapshot = match id {
+            Some(id) => snapshots.into_iter().find(|snapshot| snapshot.id == id),
+            None => snapshots.into_iter().next_back(),
+        }
+        .ok_or_else(|| match id {
+            Some(id) => anyhow!("No snapshot with id {id}; run `task undo --list` to see them"),
+            None => anyhow!("There is nothing to undo"),
+        })?;
+
+        let dir = self.root.join(&snapshot.id);
+        let mut kept = Vec::new();
+        // Later entries may sit inside earlier ones, so restore from the back.
+        for entry in snapshot.entries.iter().rev() {
+            let Some(backup) = &entry.backup else {
+                if created_since(&entry.path, snapshot.created) {
+                    remove_path(&entry.path)
+                        .with_context(|| format!("Failed to remove {}", entry.path.display()))?;
+                } else if fs::symlink_metadata(&entry.path).is_ok() {
+                    kept.push(entry.path.clone());
+                }
+                continue;
+            };
+
+            remove_path(&entry.path)
+                .with_context(|| format!("Failed to remove {}", entry.path.display()))?;
+            if let Some(parent) = entry.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            copy_tree(&dir.join("files").join(backup), &entry.path)
+                .with_context(|| format!("Failed to restore {}", entry.path.display()))?;
+        }
+
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to remove snapshot {}", dir.display()))?;
+        kept.reverse();
+        Ok(Restored { snapshot, kept })
+    }
+
+    /// Drop snapshots beyond the newest `MAX_SNAPSHOTS` and those older than `MAX_AGE_DAYS`.
+    pub fn prune(&self) -> Result<usize> {
+        let snapshots = self.list()?;
+        let cutoff = (Utc::now() - Duration::days(MAX_AGE_DAYS)).timestamp();
+        let excess = snapshots.len().saturating_sub(MAX_SNAPSHOTS);
+
+        let mut pruned = 0;
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            if index < excess || snapshot.created < cutoff {
+                fs::remove_dir_all(self.root.join(&snapshot.id))?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Time-ordered id, e.g. `20260314-093000`, with a suffix when taken within the same second.
+    fn next_id(&self) -> String {
+        let base = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut id = base.clone();
+        let mut counter = 1;
+        while self.root.join(&id).exists() {
+            id = format!("{base}-{counter:03}");
+            counter += 1;
+        }
+        id
+    }
+}
+
+/// Turn paths as written in a command into the absolute paths that exist or will be created.
+fn resolve_targets(cwd: &Path, paths: &[String]) -> Vec<PathBuf> {
+    let mut targets: Vec<PathBuf> = Vec::new();
+    for written in paths {
+        let Ok(expanded) = shellexpand::full(written) else {
+            continue;
+        };
+        let path = cwd.join(expanded.as_ref());
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let matches = if name.contains(['*', '?']) {
+            let parent = path.parent().unwrap_or(cwd).to_path_buf();
+            fs::read_dir(&parent)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter(|entry| glob_match(&name, &entry.file_name().to_string_lossy()))
+                        .map(|entry| parent.join(entry.file_name()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            vec![normalize(&path)]
+        };
+
+        for target in matches {
+            // Device nodes and kernel interfaces cannot be copied back.
+            let virtual_fs = ["/dev", "/proc", "/sys"]
+                .iter()
+                .any(|root| target.starts_with(root));
+            if !virtual_fs && !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+/// Drop `.` components and trailing slashes so `./out/` and `out` name the same entry.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Bytes stored below `path`, counted only until they exceed `limit`.
+fn disk_usage(path: &Path, limit: u64) -> u64 {
+    let mut size = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path()),
+                );
+            }
+        } else {
+            size += metadata.len();
+            if size > limit {
+                break;
+            }
+        }
+    }
+    size
+}
+
+/// Whether `path` is a regular file or directory created, or last changed, at or after the Unix
+/// timestamp `since`.
+fn created_since(path: &Path, since: i64) -> bool {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() && !metadata.is_dir() {
+        return false;
+    }
+    let since = UNIX_EPOCH + StdDuration::from_secs(since.max(0) as u64);
+    metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .is_ok_and(|time| time >= since)
+}
+
+/// Copy a file, symlink or directory tree, keeping permissions.
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(from)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, to)?;
+        #[cfg(not(unix))]
+        fs::copy(from.join(target), to)?;
+    } else if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_globs_and_relative_paths() {
+        let cwd = tempfile::tempdir().unwrap();
+        fs::write(cwd.path().join("a.txt"), "").unwrap();
+        fs::write(cwd.path().join("b.txt"), "").unwrap();
+        fs::write(cwd.path().join("c.md"), "").unwrap();
+
+        let mut targets = resolve_targets(
+            cwd.path(),
+            &["*.txt".into(), "./out/".into(), "/dev/sda".into()],
+        );
+        targets.sort();
+        assert_eq!(
+            targets,
+            [
+                cwd.path().join("a.txt"),
+                cwd.path().join("b.txt"),
+                cwd.path().join("out")
+            ]
+        );
+    }
+
+    #[test]
+    fn restores_modified_deleted_and_created_paths() {
+        let data = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(data.path());
+        let notes = work.path().join("notes.txt");
+        let tree = work.path().join("tree");
+        let created = work.path().join("created.txt");
+        fs::write(&notes, "original").unwrap();
+        fs::create_dir_all(tree.join("nested")).unwrap();
+        fs::write(tree.join("nested/leaf"), "leaf").unwrap();
+
+        let paths: Vec<String> = [&notes, &tree, &created]
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        let snapshot = store
+            .snapshot("edit things", &paths)
+            .unwrap()
+            .expect("paths were given");
+
+        fs::write(&notes, "changed").unwrap();
+        fs::remove_dir_all(&tree).unwrap();
+        fs::write(&created, "new").unwrap();
+
+        let restored = store.restore(None).unwrap();
+        assert_eq!(restored.snapshot.id, snapshot.id);
+        assert!(restored.kept.is_empty());
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "original");
+        assert_eq!(
+            fs::read_to_string(tree.join("nested/leaf")).unwrap(),
+            "leaf"
+        );
+        assert!(!created.exists());
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_paths_the_command_did_not_create() {
+        let data = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(data.path());
+        let link = work.path().join("link");
+        let created = work.path().join("created.txt");
+
+        let paths: Vec<String> = [&link, &created]
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        store.snapshot("make things", &paths).unwrap();
+
+        std::os::unix::fs::symlink(work.path(), &link).unwrap();
+        fs::write(&created, "").unwrap();
+
+        let restored = store.restore(None).unwrap();
+        assert_eq!(restored.kept, [link.as_path()]);
+        assert!(link.exists());
+        assert!(!created.exists());
+    }
+
+    #[test]
+    fn refuses_roots_and_stops_measuring_at_the_limit() {
+        let data = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(data.path());
+        assert!(store.snapshot("rm -rf /", &["/".into()]).is_err());
+        assert!(store.snapshot("rm -rf ~", &["~".into()]).is_err());
+
+        let work = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(work.path().join(name), "0123456789").unwrap();
+        }
+        assert_eq!(disk_usage(work.path(), 100), 30);
+        assert!(disk_usage(work.path(), 15) < 30);
+    }
+
+    #[test]
+    fn prunes_beyond_the_limit() {
+        let data = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(data.path());
+        let path = work.path().join("f").display().to_string();
+
+        for _ in 0..MAX_SNAPSHOTS + 3 {
+            store
+                .snapshot("touch f", std::slice::from_ref(&path))
+                .unwrap();
+        }
+        assert_eq!(store.list().unwrap().len(), MAX_SNAPSHOTS);
+    }
+}
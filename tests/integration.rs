@@ -1,8 +1,66 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
 use assert_cmd::Command;
+use predicates::prelude::PredicateBooleanExt;
 use predicates::str::contains;
 
 const BIN: &str = "task";
 
+// Exit codes documented in docs/src/api.md.
+const GENERATION_FAILED: i32 = 80;
+const AUTH_FAILED: i32 = 81;
+const BLOCKED: i32 = 82;
+const DECLINED: i32 = 83;
+const NOT_INTERACTIVE: i32 = 84;
+const TIMED_OUT: i32 = 124;
+
+/// Answer a single HTTP request with a canned JSON body and return the server's base URL.
+fn serve_once(status: u16, body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut buffer = [0u8; 8192];
+        let mut request = Vec::new();
+        loop {
+            let read = socket.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_lowercase();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if read == 0 || request.len() >= end + 4 + length {
+                    break;
+                }
+            } else if read == 0 {
+                break;
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 {status} Stub\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        socket.write_all(response.as_bytes()).unwrap();
+    });
+
+    format!("http://{addr}")
+}
+
+/// Whether util-linux `script` is available to give `task` a terminal.
+#[cfg(target_os = "linux")]
+fn has_script() -> bool {
+    std::process::Command::new("script")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
 #[test]
 fn displays_help() {
     Command::cargo_bin(BIN)
@@ -13,6 +71,100 @@ fn displays_help() {
         .stdout(contains("EXAMPLES:"));
 }
 
+#[test]
+fn generates_fish_completions() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["completions", "fish"])
+        .assert()
+        .success()
+        .stdout(contains("complete -c task"));
+}
+
+#[test]
+fn init_prints_key_bindings_that_use_emit_only() {
+    for (shell, binding) in [
+        ("zsh", "zle -N _task_sh_widget"),
+        ("bash", "bind -x"),
+        ("fish", "commandline --replace"),
+        ("nu", "commandline edit --replace"),
+    ] {
+        Command::cargo_bin(BIN)
+            .unwrap()
+            .args(["init", shell])
+            .assert()
+            .success()
+            .stdout(contains(binding))
+            .stdout(contains("--emit-only"));
+    }
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["init", "sh"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn emit_only_prints_just_the_command() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "--emit-only", "--shell", "bash", "--", "list files"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: ls -la\nExplanation: lists files",
+        )
+        .assert()
+        .success()
+        .stdout("ls -la\n");
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "--emit-only", "wipe the disk"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: rm -rf /")
+        .assert()
+        .code(BLOCKED)
+        .stdout("");
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "--emit-only", "delete the build log"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: rm build.log")
+        .assert()
+        .code(BLOCKED)
+        .stdout("")
+        .stderr(contains("needs confirmation"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn emit_only_never_prompts_for_an_api_key() {
+    if !has_script() {
+        return;
+    }
+    let home = tempfile::tempdir().unwrap();
+
+    // On a terminal, `task gen` would ask for the key; the widget must get an error instead.
+    Command::new("script")
+        .args([
+            "-qec",
+            &format!(
+                "{} gen --emit-only --backend openai 'list files'",
+                env!("CARGO_BIN_EXE_task")
+            ),
+            "/dev/null",
+        ])
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("OPENAI_API_KEY")
+        .env_remove("TASK_SH_FAKE_RESPONSE")
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .code(AUTH_FAILED)
+        .stdout(contains("OPENAI_API_KEY is not set"))
+        .stdout(contains("API key:").not());
+}
+
 #[test]
 fn warns_on_empty_description() {
     Command::cargo_bin(BIN)
@@ -23,7 +175,7 @@ fn warns_on_empty_description() {
             "Command: echo noop\nExplanation: noop",
         )
         .assert()
-        .success()
+        .code(GENERATION_FAILED)
         .stdout(contains("Please provide more details"));
 }
 
@@ -38,8 +190,27 @@ fn accepts_stdin_description() {
         )
         .write_stdin("check disk usage")
         .assert()
-        .success()
-        .stdout(contains("Suggested command"));
+        .code(NOT_INTERACTIVE)
+        .stdout(contains("Suggested command"))
+        .stdout(contains("No answer on stdin; command not executed."));
+}
+
+#[test]
+fn confirming_through_a_pipe_does_not_run_the_command() {
+    let work = tempfile::tempdir().unwrap();
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(work.path())
+        .args(["gen", "create a marker file", "--shell", "sh"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: touch marker")
+        .write_stdin("y\n")
+        .assert()
+        .code(NOT_INTERACTIVE)
+        .stdout(contains(
+            "Non-interactive session detected; skipping execution.",
+        ));
+    assert!(!work.path().join("marker").exists());
 }
 
 #[test]
@@ -52,7 +223,599 @@ fn verbose_mode_shows_raw_response() {
             "Command: ls -la\nExplanation: Lists files",
         )
         .assert()
-        .success()
+        .code(NOT_INTERACTIVE)
         .stdout(contains("Raw response:"))
         .stdout(contains("Explanation:"));
 }
+
+#[test]
+fn detects_target_shell_from_environment() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files", "--verbose"])
+        .env("SHELL", "/usr/local/bin/fish")
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: ls -la\nExplanation: Lists files",
+        )
+        .assert()
+        .code(NOT_INTERACTIVE)
+        .stdout(contains("Suggested command (fish)"))
+        .stdout(contains("detected from $SHELL"));
+}
+
+#[test]
+fn stream_mode_previews_response_before_suggestion() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files", "--stream"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: ls -la\nExplanation: Lists files",
+        )
+        .assert()
+        .code(NOT_INTERACTIVE)
+        .stderr(contains("Command: ls -la"))
+        .stdout(contains("Suggested command"));
+}
+
+#[test]
+fn structured_response_flags_reach_json_output() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "clean temp files", "--json"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            r#"{"command":"find . -name '*.tmp' -delete","alternatives":[],"explanation":"Deletes temp files","confidence":0.9,"requires_sudo":false,"destructive":true}"#,
+        )
+        .assert()
+        .success()
+        .stdout(contains(r#""destructive": true"#))
+        .stdout(contains(r#""confidence": "needs_confirmation""#));
+}
+
+#[test]
+fn anthropic_backend_end_to_end() {
+    let base_url = serve_once(
+        200,
+        r#"{"id":"msg_1","type":"message","role":"assistant","content":[{"type":"text","text":"Command: ls -la\nExplanation: Lists files"}],"stop_reason":"end_turn"}"#,
+    );
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files", "--backend", "anthropic"])
+        .env("ANTHROPIC_API_KEY", "sk-ant-test")
+        .env("ANTHROPIC_BASE_URL", base_url)
+        .env_remove("TASK_SH_FAKE_RESPONSE")
+        .assert()
+        .code(NOT_INTERACTIVE)
+        .stdout(contains("ls -la"));
+}
+
+#[test]
+fn falls_back_through_provider_chain_and_reports_provider_in_json() {
+    let base_url = serve_once(429, r#"{"error":"rate limited"}"#);
+    let data_dir = tempfile::tempdir().unwrap();
+    let config = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        config.path(),
+        format!(
+            "[local]\nbase_url = \"{base_url}\"\n\n[[providers]]\nbackend = \"llama-cpp\"\n\n[[providers]]\nbackend = \"offline\"\n"
+        ),
+    )
+    .unwrap();
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["--config", config.path().to_str().unwrap()])
+        .args(["gen", "check disk usage", "--json"])
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .env_remove("TASK_SH_FAKE_RESPONSE")
+        .assert()
+        .success()
+        .stdout(contains(r#""command": "du -sh *""#))
+        .stdout(contains(r#""provider": "offline:rules""#));
+
+    assert!(data_dir.path().join("health.toml").exists());
+}
+
+#[test]
+fn policy_check_applies_project_rules() {
+    let project = tempfile::tempdir().unwrap();
+    std::fs::write(
+        project.path().join(".task-policy.toml"),
+        "[[deny]]\ncommand = \"kubectl\"\nargs = [\"delete\"]\nreason = \"use the deploy pipeline\"\n\n[[allow]]\ncommand = \"rm\"\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(project.path())
+        .env("XDG_CONFIG_HOME", project.path())
+        .args(["policy", "check", "kubectl delete pod web-1"])
+        .assert()
+        .success()
+        .stdout(contains("Verdict: deny"))
+        .stdout(contains("use the deploy pipeline"))
+        .stdout(contains(".task-policy.toml (project)"));
+
+    // Project files cannot waive built-in rules.
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(project.path())
+        .env("XDG_CONFIG_HOME", project.path())
+        .args(["policy", "check", "rm -rf /"])
+        .assert()
+        .success()
+        .stdout(contains("Verdict: deny"))
+        .stdout(contains("built-in rule"));
+}
+
+#[test]
+fn outside_project_guard_blocks_writes_above_the_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("project");
+    std::fs::create_dir_all(project.join(".git")).unwrap();
+    std::fs::write(
+        project.join(".task-policy.toml"),
+        "outside_project = \"deny\"\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(&project)
+        .env("XDG_CONFIG_HOME", dir.path())
+        .args(["gen", "back up the notes", "--json"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: cp notes.txt ../notes.txt",
+        )
+        .assert()
+        .code(BLOCKED)
+        .stderr(contains("blocked by safety rules"))
+        .stderr(contains("Writes outside the project to"));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(&project)
+        .env("XDG_CONFIG_HOME", dir.path())
+        .args(["gen", "back up the notes", "--json"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: cp notes.txt backup/notes.txt",
+        )
+        .assert()
+        .success();
+}
+
+#[test]
+fn unsafe_alternatives_are_annotated_and_not_selectable() {
+    let response =
+        "Command: df -h\nCommands:\n1. du -sh *\n2. sudo rm -rf ~\nExplanation: disk usage";
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "check disk usage", "--json"])
+        .env("TASK_SH_FAKE_RESPONSE", response)
+        .assert()
+        .success()
+        .stdout(contains(
+            r#""alternatives": [
+    "du -sh *"
+  ]"#,
+        ))
+        .stdout(contains(r#""command": "sudo rm -rf ~""#));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "check disk usage", "--shell", "bash"])
+        .env("TASK_SH_FAKE_RESPONSE", response)
+        .write_stdin("3\n0\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("sudo rm -rf ~  [blocked: "))
+        .stdout(contains("That command is blocked by safety rules"))
+        .stdout(contains("No command selected"));
+}
+
+#[test]
+fn destructive_command_shows_report_and_requires_typed_name() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "remove build directory", "--shell", "bash"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: rm -r build\nExplanation: Removes build",
+        )
+        .write_stdin("y\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("Risk: destructive. Deletes or overwrites data"))
+        .stdout(contains("Affects build."))
+        .stdout(contains("Type 'rm' to proceed"))
+        .stdout(contains("Command not executed."));
+}
+
+#[test]
+fn dry_run_reports_changes_without_touching_files() {
+    let project = tempfile::tempdir().unwrap();
+    std::fs::write(project.path().join("kept.txt"), "keep\n").unwrap();
+    std::fs::write(project.path().join("gone.txt"), "").unwrap();
+
+    let output = Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(project.path())
+        .args(["gen", "shuffle some files", "--shell", "sh", "--dry-run"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: echo new > created.txt; rm gone.txt; echo more >> kept.txt\nExplanation: test",
+        )
+        .write_stdin("n\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("Command not executed."))
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        std::fs::read_to_string(project.path().join("kept.txt")).unwrap(),
+        "keep\n"
+    );
+    assert!(project.path().join("gone.txt").exists());
+    assert!(!project.path().join("created.txt").exists());
+
+    // Some CI runners disable unprivileged user namespaces.
+    let stdout = String::from_utf8(output).unwrap();
+    if !stdout.contains("Dry run failed") {
+        assert!(stdout.contains("created  created.txt"), "{stdout}");
+        assert!(stdout.contains("modified kept.txt"), "{stdout}");
+        assert!(stdout.contains("deleted  gone.txt"), "{stdout}");
+    }
+}
+
+#[test]
+fn dry_run_is_isolated_and_stopped_at_the_timeout() {
+    let project = tempfile::tempdir().unwrap();
+
+    let output = Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(project.path())
+        .args([
+            "gen",
+            "wait around for a while",
+            "--shell",
+            "sh",
+            "--dry-run",
+            "--exec-timeout",
+            "500ms",
+        ])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: echo pid $$; ls -A /run | wc -l; touch out.txt; sleep 30\nExplanation: test",
+        )
+        .write_stdin("n\n")
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .code(DECLINED)
+        .get_output()
+        .stdout
+        .clone();
+
+    let stdout = String::from_utf8(output).unwrap();
+    if !stdout.contains("Dry run failed") {
+        assert!(stdout.contains("pid 1\n0\n"), "{stdout}");
+        assert!(stdout.contains("Dry run timed out after 500ms"), "{stdout}");
+        assert!(stdout.contains("created  out.txt"), "{stdout}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn dry_run_cannot_reach_sockets_in_tmp() {
+    let project = tempfile::tempdir().unwrap();
+    let sockets = tempfile::Builder::new().tempdir_in("/tmp").unwrap();
+    let socket = sockets.path().join("agent.sock");
+    let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+
+    let output = Command::cargo_bin(BIN)
+        .unwrap()
+        .current_dir(project.path())
+        .args(["gen", "talk to the agent", "--shell", "sh", "--dry-run"])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            format!(
+                "Command: test -S {} && echo reachable || echo hidden; touch out.txt\nExplanation: test",
+                socket.display()
+            ),
+        )
+        .write_stdin("n\n")
+        .assert()
+        .code(DECLINED)
+        .get_output()
+        .stdout
+        .clone();
+
+    let stdout = String::from_utf8(output).unwrap();
+    if !stdout.contains("Dry run failed") {
+        assert!(stdout.contains("\nhidden\n"), "{stdout}");
+        assert!(!stdout.contains("\nreachable\n"), "{stdout}");
+        assert!(stdout.contains("created  out.txt"), "{stdout}");
+    }
+}
+
+#[test]
+fn dry_run_is_skipped_for_commands_that_act_on_processes() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args([
+            "gen",
+            "stop every node process",
+            "--shell",
+            "sh",
+            "--dry-run",
+        ])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: pkill node\nExplanation: test",
+        )
+        .write_stdin("n\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("Skipping the dry run: `pkill`"))
+        .stdout(contains("Dry run:").not());
+}
+
+#[test]
+fn undo_reports_when_nothing_is_stored() {
+    let data_dir = tempfile::tempdir().unwrap();
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["undo", "--list"])
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .assert()
+        .success()
+        .stdout(contains("No snapshots stored."));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .arg("undo")
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .assert()
+        .failure()
+        .stderr(contains("There is nothing to undo"));
+}
+
+#[test]
+fn show_redactions_lists_masked_values() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args([
+            "gen",
+            "email the report to ops@example.com",
+            "--json",
+            "--show-redactions",
+        ])
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: mail -s report __EMAIL_1__ < report.txt",
+        )
+        .assert()
+        .success()
+        .stdout(contains(
+            r#""command": "mail -s report ops@example.com < report.txt""#,
+        ))
+        .stdout(contains(r#""placeholder": "__EMAIL_1__""#))
+        .stdout(contains(r#""kind": "email""#));
+}
+
+#[test]
+fn exit_codes_distinguish_outcomes() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "wipe the disk"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: rm -rf /")
+        .assert()
+        .code(BLOCKED);
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: ls -la")
+        .write_stdin("n\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("Command not executed."));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files"])
+        .env("TASK_SH_FAKE_RESPONSE", "")
+        .assert()
+        .code(GENERATION_FAILED)
+        .stderr(contains("Failed to generate command"));
+
+    let base_url = serve_once(
+        401,
+        r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#,
+    );
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files", "--backend", "openai"])
+        .env("OPENAI_API_KEY", "sk-wrong")
+        .env("OPENAI_BASE_URL", base_url)
+        .env_remove("TASK_SH_FAKE_RESPONSE")
+        .assert()
+        .code(AUTH_FAILED)
+        .stderr(contains("Authentication failed"));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files", "--backend", "openai"])
+        .env_remove("OPENAI_API_KEY")
+        .env_remove("TASK_SH_FAKE_RESPONSE")
+        .assert()
+        .code(AUTH_FAILED)
+        .stderr(contains("OPENAI_API_KEY is not set"));
+}
+
+#[test]
+fn edited_commands_are_checked_again() {
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: ls -la")
+        .write_stdin("e\nls -lah\nn\n")
+        .assert()
+        .code(DECLINED)
+        .stdout(contains("[y/N/e]"))
+        .stdout(contains("-c \"ls -lah\""));
+
+    Command::cargo_bin(BIN)
+        .unwrap()
+        .args(["gen", "list files"])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: ls -la")
+        .write_stdin("e\nrm -rf /\n")
+        .assert()
+        .code(BLOCKED)
+        .stdout(contains(
+            "Refusing to run a command blocked by safety rules.",
+        ));
+}
+
+/// Runs `task` under `script` so it has a terminal and actually executes the command.
+#[cfg(target_os = "linux")]
+#[test]
+fn executed_command_exit_code_is_passed_through() {
+    if !has_script() {
+        return;
+    }
+    let data_dir = tempfile::tempdir().unwrap();
+
+    Command::new("script")
+        .args([
+            "-qec",
+            &format!(
+                "{} gen 'fail on purpose' --shell sh",
+                env!("CARGO_BIN_EXE_task")
+            ),
+            "/dev/null",
+        ])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: sh -c 'exit 7'")
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .write_stdin("y\n")
+        .assert()
+        .code(7)
+        .stdout(contains("Command exited with status"));
+}
+
+/// The command only finishes once the test has seen its first line, so this hangs unless output
+/// is passed on while the command runs.
+#[cfg(target_os = "linux")]
+#[test]
+fn streams_output_while_the_command_runs() {
+    use std::process::Stdio;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    if !has_script() {
+        return;
+    }
+    let work = tempfile::tempdir().unwrap();
+    let data_dir = tempfile::tempdir().unwrap();
+
+    let mut child = std::process::Command::new("script")
+        .args([
+            "-qec",
+            &format!(
+                "{} gen 'print in steps' --shell sh",
+                env!("CARGO_BIN_EXE_task")
+            ),
+            "/dev/null",
+        ])
+        .current_dir(work.path())
+        .env(
+            "TASK_SH_FAKE_RESPONSE",
+            "Command: sleep 0.5; echo one; until [ -e go ]; do sleep 0.05; done; echo two >&2; sleep 0.2; echo three; exit 7",
+        )
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+
+    let output = Arc::new(Mutex::new(String::new()));
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = {
+        let output = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+                output
+                    .lock()
+                    .unwrap()
+                    .push_str(&String::from_utf8_lossy(&buffer[..read]));
+            }
+        })
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !output.lock().unwrap().contains("one\r\n") {
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("first line never arrived: {:?}", output.lock().unwrap());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    std::fs::write(work.path().join("go"), "").unwrap();
+
+    let status = child.wait().unwrap();
+    reader.join().unwrap();
+    let output = output.lock().unwrap().clone();
+    assert_eq!(status.code(), Some(7), "{output}");
+
+    let one = output.find("one\r\n").unwrap();
+    let two = output.find("two\r\n").expect("stderr is shown");
+    let three = output
+        .find("three\r\n")
+        .expect("stdout after stderr is shown");
+    assert!(one < two && two < three, "{output}");
+
+    // The spinner ran while the command was silent and was cleared before its first byte.
+    let spinner = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    assert!(output[..one].contains(spinner), "{output}");
+    assert!(!output[one..].contains(spinner), "{output}");
+    assert!(
+        output[one..].contains("Command exited with status"),
+        "{output}"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn exec_timeout_stops_the_command_and_its_children() {
+    if !has_script() {
+        return;
+    }
+    let data_dir = tempfile::tempdir().unwrap();
+
+    Command::new("script")
+        .args([
+            "-qec",
+            &format!(
+                "{} gen 'wait a while' --shell sh --exec-timeout 300ms",
+                env!("CARGO_BIN_EXE_task")
+            ),
+            "/dev/null",
+        ])
+        .env("TASK_SH_FAKE_RESPONSE", "Command: sleep 30 & sleep 30")
+        .env("TASK_SH_DATA_DIR", data_dir.path())
+        .write_stdin("y\n")
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .code(TIMED_OUT)
+        .stdout(contains("Command timed out after 300ms"));
+}